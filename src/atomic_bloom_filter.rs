@@ -0,0 +1,220 @@
+use crate::{bit_index, BloomFilter};
+use aabel_multihash_rs::{BuildHasherExt, Hash64, HasherExt};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Implements a [`BloomFilter`] which can be shared between threads without a lock. The bits are stored in
+/// atomic 64 bits words, so [`AtomicBloomFilter::insert`] and [`AtomicBloomFilter::contains`] take `&self`
+/// and the filter is `Send` and `Sync`, for example behind an `Arc`.
+/// The [`K`] and [`H`] generic arguments and the bits set for each item are the ones of [`BloomFilter`],
+/// and [`AtomicBloomFilter::snapshot_with`] copies the bits into a plain [`BloomFilter`], which can be serialized.
+///
+/// The words are read and updated with relaxed atomic operations. An item inserted by a thread is seen by the
+/// other threads once they synchronize with it, for example after joining it; a concurrent
+/// [`AtomicBloomFilter::contains`] may miss an insertion in progress.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::AtomicBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let filter = AtomicBloomFilter::<u32, _>::new(builder);
+///
+/// std::thread::scope(|s| {
+///     s.spawn(|| (0..100u32).for_each(|i| filter.insert(&i)));
+///     s.spawn(|| (100..200u32).for_each(|i| filter.insert(&i)));
+/// });
+///
+/// assert!((0..200u32).all(|i| filter.contains(&i)));
+///```
+pub struct AtomicBloomFilter<T, B, const K: usize = 100, const H: usize = 10>
+where
+    T: ?Sized,
+{
+    builder: B,
+    words: [AtomicU64; K],
+    // The filter does not hold any item, so it is `Send` and `Sync` whatever the type of the items.
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T, B, const K: usize, const H: usize> AtomicBloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`AtomicBloomFilter`] instance based on a given [`BuildHasherExt`] instance.
+    pub fn new(builder: B) -> Self {
+        Self::from_words([0; K], builder)
+    }
+
+    /// Creates a new [`AtomicBloomFilter`] instance from the words of another filter, see [`BloomFilter::as_words`].
+    pub fn from_words(words: [u64; K], builder: B) -> Self {
+        Self {
+            builder,
+            words: words.map(AtomicU64::new),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a copy of the words of the filter, laid out like [`BloomFilter::as_words`]. Each word is
+    /// loaded on its own, so the copy may include only part of the items inserted while it is taken.
+    pub fn to_words(&self) -> [u64; K] {
+        std::array::from_fn(|i| self.words[i].load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of bits set in the filter.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+
+    /// Copies the bits of the filter into a [`BloomFilter`] with a clone of the hasher builder. For the builders
+    /// which cannot be cloned, like the ones of `aabel_multihash_rs`, see [`AtomicBloomFilter::snapshot_with`].
+    pub fn snapshot(&self) -> BloomFilter<T, B, K, H>
+    where
+        B: Clone,
+    {
+        self.snapshot_with(self.builder.clone())
+    }
+
+    /// Copies the bits of the filter into a [`BloomFilter`] with a given hasher builder, which is expected
+    /// to generate the same hash values as the one of the filter.
+    ///
+    /// # Example
+    ///
+    ///```
+    /// use aabel_bloom_rs::AtomicBloomFilter;
+    /// use aabel_multihash_rs::BuildPairHasher;
+    ///
+    /// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+    /// let filter = AtomicBloomFilter::<str, _>::new(builder());
+    /// filter.insert("Hello world!");
+    ///
+    /// let mut snapshot = filter.snapshot_with(builder());
+    /// assert!(snapshot.contains("Hello world!"));
+    ///```
+    pub fn snapshot_with(&self, builder: B) -> BloomFilter<T, B, K, H> {
+        BloomFilter::from_words(self.to_words(), builder)
+    }
+
+    /// Converts the filter into a [`BloomFilter`] with the same bits and hasher builder.
+    pub fn into_bloom_filter(self) -> BloomFilter<T, B, K, H> {
+        BloomFilter::from_words(self.words.map(AtomicU64::into_inner), self.builder)
+    }
+}
+
+impl<T, B, const K: usize, const H: usize> AtomicBloomFilter<T, B, K, H>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item. The bits are set with `fetch_or`, so several threads can insert
+    /// items at the same time.
+    pub fn insert<U>(&self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let set_bit_for_hash = |hash: Hash64| {
+            let index = bit_index(hash, 64 * K);
+            self.words[index / 64].fetch_or(1 << (index % 64), Ordering::Relaxed);
+        };
+
+        self.builder
+            .hashes_one(item)
+            .take(H)
+            .for_each(set_bit_for_hash);
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let get_bit_for_hash = |hash: Hash64| {
+            let index = bit_index(hash, 64 * K);
+            self.words[index / 64].load(Ordering::Relaxed) & (1 << (index % 64)) != 0
+        };
+
+        self.builder.hashes_one(item).take(H).all(get_bit_for_hash)
+    }
+}
+
+impl<T, B, const K: usize, const H: usize> From<BloomFilter<T, B, K, H>>
+    for AtomicBloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    fn from(filter: BloomFilter<T, B, K, H>) -> Self {
+        Self::from_words(filter.bits.data, filter.builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+    use std::{cell::Cell, sync::Arc};
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+
+        // The items themselves do not have to be shared between threads.
+        let filter = AtomicBloomFilter::<Cell<u32>, _>::new(builder());
+        assert_send_sync(&filter);
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = Arc::new(AtomicBloomFilter::<u32, _, 10, 4>::new(builder()));
+
+        let handles: Vec<_> = (0..4u32)
+            .map(|t| {
+                let filter = Arc::clone(&filter);
+                std::thread::spawn(move || (100 * t..100 * (t + 1)).for_each(|i| filter.insert(&i)))
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        assert!((0..400u32).all(|i| filter.contains(&i)));
+
+        // The bits are the ones set by a plain filter.
+        let mut plain = BloomFilter::<u32, _, 10, 4>::new(builder());
+        (0..400u32).for_each(|i| plain.insert(&i));
+        assert_eq!(&filter.to_words(), plain.as_words());
+        assert_eq!(filter.count_ones(), plain.count_ones());
+    }
+
+    #[test]
+    fn conversions() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut plain = BloomFilter::<str, _, 3, 4>::new(builder());
+        plain.insert("Hello world!");
+        let words = *plain.as_words();
+
+        let filter = AtomicBloomFilter::from(plain);
+        assert!(filter.contains("Hello world!"));
+        filter.insert("Rust rocks");
+
+        let mut plain = filter.into_bloom_filter();
+        assert!(plain.contains("Hello world!"));
+        assert!(plain.contains("Rust rocks"));
+        assert!(plain.count_ones() > words.iter().map(|w| w.count_ones() as usize).sum());
+    }
+}
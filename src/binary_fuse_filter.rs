@@ -0,0 +1,313 @@
+use crate::{
+    builder_fingerprint,
+    xor_filter::{assign_fingerprints, first_hash, key_hashes, mix, MAX_ATTEMPTS},
+    Error, Fingerprint,
+};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The maximum number of slots of a segment.
+const MAX_SEGMENT_LEN: usize = 1 << 18;
+
+/// Implements the [binary fuse filter](https://doi.org/10.1145/3510449) of Graf and Lemire, a static filter
+/// built once from a complete set of keys. The slots are split into many small segments and each key is
+/// mapped to one slot in each of three consecutive segments, such that the xor of the three fingerprints
+/// is the fingerprint of the key.
+///
+/// For large sets, it uses about `1.125 F` bits per key, where `F` is the number of bits of the
+/// [`Fingerprint`], which is less than a [`XorFilter`](crate::XorFilter), and its false-positive
+/// probability is `2^-F`.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::BinaryFuseFilter8;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let filter = BinaryFuseFilter8::<u32, _>::new(0..100_000u32, builder).unwrap();
+///
+/// assert!(filter.contains(&42));
+/// assert!(filter.bits_per_key() < 10.0);
+///```
+pub struct BinaryFuseFilter<T, B, F = u8>
+where
+    T: ?Sized,
+{
+    builder: B,
+    seed: u64,
+    fingerprints: Vec<F>,
+    segment_len: usize,
+    segments: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+/// A [`BinaryFuseFilter`] with fingerprints of 8 bits.
+pub type BinaryFuseFilter8<T, B> = BinaryFuseFilter<T, B, u8>;
+
+/// A [`BinaryFuseFilter`] with fingerprints of 16 bits.
+pub type BinaryFuseFilter16<T, B> = BinaryFuseFilter<T, B, u16>;
+
+impl<T, B, F> BinaryFuseFilter<T, B, F>
+where
+    T: ?Sized,
+    F: Fingerprint,
+{
+    /// Returns the number of distinct keys in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the filter was built from an empty set of keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn bit_len(&self) -> usize {
+        self.fingerprints.len() * F::BITS as usize
+    }
+
+    /// Returns the number of bits of the filter for each key.
+    pub fn bits_per_key(&self) -> f64 {
+        self.bit_len() as f64 / self.len.max(1) as f64
+    }
+
+    /// Returns the false-positive probability `2^-F`.
+    pub fn false_positive_rate(&self) -> f64 {
+        0.5f64.powi(F::BITS as i32)
+    }
+
+    /// Computes the length of a segment and the number of segments where the first slot of a key can be.
+    fn geometry(keys: usize) -> (usize, usize) {
+        if keys <= 1 {
+            return (4, 1);
+        }
+
+        let n = keys as f64;
+        let segment_len =
+            (1usize << (n.ln() / 3.33f64.ln() + 2.25).floor() as u32).min(MAX_SEGMENT_LEN);
+        let size_factor = (0.875 + 0.25 * 1e6f64.ln() / n.ln()).max(1.125);
+        let capacity = (n * size_factor).round() as usize;
+
+        // The last two segments only hold the second and third slots of the keys.
+        let segments = capacity.div_ceil(segment_len).saturating_sub(2).max(1);
+        (segment_len, segments)
+    }
+
+    fn positions(hash: u64, segment_len: usize, segments: usize) -> [usize; 3] {
+        let mask = (segment_len - 1) as u64;
+        let first = ((hash as u128 * (segments * segment_len) as u128) >> 64) as usize;
+
+        [
+            first,
+            (first + segment_len) ^ ((hash >> 18) & mask) as usize,
+            (first + 2 * segment_len) ^ (hash & mask) as usize,
+        ]
+    }
+}
+
+impl<T, B, F> BinaryFuseFilter<T, B, F>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+    F: Fingerprint,
+{
+    /// Builds a new [`BinaryFuseFilter`] instance from a set of keys. Duplicated keys are ignored.
+    /// It returns [`Error::ConstructionFailed`] when no seed derived from the hasher builder
+    /// leads to a valid filter, which is very unlikely.
+    pub fn new<I>(keys: I, builder: B) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let hashes = key_hashes(keys, &builder);
+        let (segment_len, segments) = Self::geometry(hashes.len());
+        let slots = (segments + 2) * segment_len;
+        let mut rng = StdRng::seed_from_u64(builder_fingerprint(&builder));
+
+        for _ in 0..MAX_ATTEMPTS {
+            let seed = rng.gen();
+            let mixed = hashes
+                .iter()
+                .map(|hash| mix(*hash, seed))
+                .collect::<Vec<_>>();
+            let positions = |hash| Self::positions(hash, segment_len, segments);
+
+            if let Some(fingerprints) = assign_fingerprints(&mixed, slots, positions) {
+                return Ok(Self {
+                    builder,
+                    seed,
+                    fingerprints,
+                    segment_len,
+                    segments,
+                    len: hashes.len(),
+                    _marker: PhantomData,
+                });
+            }
+        }
+
+        Err(Error::ConstructionFailed)
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let hash = mix(first_hash(item, &self.builder), self.seed);
+        let [a, b, c] = Self::positions(hash, self.segment_len, self.segments);
+
+        F::from_hash(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, check_size, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`BinaryFuseFilter`]. The width of the fingerprints rejects a filter deserialized
+    /// with a different [`Fingerprint`] type.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        fingerprint_bits: u32,
+        seed: u64,
+        segment_len: usize,
+        segments: usize,
+        len: usize,
+        fingerprints: Bytes,
+    }
+
+    impl<T, B, F> Serialize for BinaryFuseFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let fingerprints = self
+                .fingerprints
+                .iter()
+                .map(|fingerprint| (*fingerprint).into());
+
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                fingerprint_bits: F::BITS,
+                seed: self.seed,
+                segment_len: self.segment_len,
+                segments: self.segments,
+                len: self.len,
+                fingerprints: Bytes::from_values(fingerprints, F::BITS),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B, F> Deserialize<'de> for BinaryFuseFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B, F> BinaryFuseFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the fingerprints have a different width or when the filter was built with a different
+        /// hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_size(repr.fingerprint_bits, F::BITS)?;
+            check_hasher(repr.hasher, &builder)?;
+
+            if !repr.segment_len.is_power_of_two() {
+                return Err(Error::InvalidFormat("segment length"));
+            }
+
+            if repr.segments == 0 {
+                return Err(Error::ZeroSize("number of segments"));
+            }
+
+            let slots = repr
+                .segments
+                .saturating_add(2)
+                .saturating_mul(repr.segment_len);
+            let fingerprints = repr
+                .fingerprints
+                .into_values(slots, F::BITS)?
+                .into_iter()
+                .map(|value| F::try_from(value).unwrap_or_default())
+                .collect();
+
+            Ok(Self {
+                builder,
+                seed: repr.seed,
+                fingerprints,
+                segment_len: repr.segment_len,
+                segments: repr.segments,
+                len: repr.len,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn no_false_negatives() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = BinaryFuseFilter8::<u32, _>::new(0..100_000u32, builder).unwrap();
+
+        assert_eq!(filter.len(), 100_000);
+        assert!((0..100_000u32).all(|i| filter.contains(&i)));
+
+        let false_positives = (100_000..200_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 600);
+    }
+
+    #[test]
+    fn small_sets() {
+        for len in 0..100u32 {
+            let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+            let filter = BinaryFuseFilter16::<u32, _>::new(0..len, builder).unwrap();
+
+            assert!((0..len).all(|i| filter.contains(&i)));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{params, Error};
+use aabel_multihash_rs::{BuildHasherExt, Hash64, HasherExt};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The number of bits in a block, the size of a cache line.
+pub const BLOCK_BITS: usize = 512;
+
+/// A cache line of bits.
+#[derive(Clone, Copy, Default)]
+#[repr(align(64))]
+struct Block([u64; BLOCK_BITS / 64]);
+
+impl Block {
+    /// Returns the block selected by the first hash value of an item. The block is taken from the high bits
+    /// of the hash value, because the bits inside the block are taken from the low bits of the next ones,
+    /// which depend on the low bits of the first one.
+    fn select(hash: Hash64, blocks: usize) -> usize {
+        let hash: u64 = hash.into();
+        ((hash as u128 * blocks as u128) >> 64) as usize
+    }
+
+    fn index(hash: Hash64) -> (usize, u64) {
+        let hash: u64 = hash.into();
+        let bit = hash % BLOCK_BITS as u64;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn set(&mut self, hash: Hash64) {
+        let (word, mask) = Self::index(hash);
+        self.0[word] |= mask;
+    }
+
+    fn get(&self, hash: Hash64) -> bool {
+        let (word, mask) = Self::index(hash);
+        self.0[word] & mask != 0
+    }
+}
+
+/// Implements the blocked bloom filter of [Putze et al.](https://doi.org/10.1145/1498698.1594230).
+/// The first hash value of an item selects a block of [`BLOCK_BITS`] bits, the size of a cache line,
+/// and the next `hashes` hash values select the bits inside that block. So [`BlockedBloomFilter::insert`]
+/// and [`BlockedBloomFilter::contains`] touch a single cache line, while the classic layout touches up to
+/// one cache line for each hash value.
+///
+/// The price is a higher false-positive probability for the same number of bits, because the items are not
+/// evenly spread across the blocks and the fuller blocks answer true more often. The difference is small for
+/// false-positive rates around 1% and grows as the target rate gets lower, see
+/// [`params::blocked_false_positive_rate`]. [`BlockedBloomFilter::with_capacity_and_fpr`] adds blocks until
+/// the target rate is met, which takes about 5% more bits than the classic layout at 1% and 20% more at 0.01%.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::BlockedBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = BlockedBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+///
+/// filter.insert("Hello world!");
+/// assert!(filter.contains("Hello world!"));
+///```
+pub struct BlockedBloomFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    blocks: Vec<Block>,
+    hashes: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> BlockedBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+{
+    /// Creates a new [`BlockedBloomFilter`] instance with a given number of blocks and hash values
+    /// set inside the block of each item.
+    pub fn new(blocks: usize, hashes: usize, builder: B) -> Result<Self, Error> {
+        if blocks == 0 {
+            return Err(Error::ZeroSize("number of blocks"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        Ok(Self {
+            builder,
+            blocks: vec![Block::default(); blocks],
+            hashes,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`BlockedBloomFilter`] instance sized to hold `items` items while keeping
+    /// the false-positive probability of the blocked layout under `fpr`.
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        if items == 0 {
+            return Err(Error::ZeroSize("number of items"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        let hashes = params::optimal_hashes_for_fpr(fpr);
+        let mut blocks = params::optimal_bits(items, fpr).div_ceil(BLOCK_BITS);
+        while params::blocked_false_positive_rate(blocks, BLOCK_BITS, hashes, items) > fpr {
+            blocks += (blocks / 16).max(1);
+        }
+
+        Self::new(blocks, hashes, builder)
+    }
+
+    /// Returns the number of blocks in the filter.
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn bit_len(&self) -> usize {
+        self.blocks.len() * BLOCK_BITS
+    }
+
+    /// Returns the number of hash values set inside the block of each item.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+}
+
+impl<T, B> BlockedBloomFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let mut hashes = self.builder.hashes_one(item);
+        let first = hashes.next().expect("the hash sequence is infinite");
+        let index = Block::select(first, self.blocks.len());
+        let block = &mut self.blocks[index];
+
+        hashes.take(self.hashes).for_each(|hash| block.set(hash));
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let mut hashes = self.builder.hashes_one(item);
+        let first = hashes.next().expect("the hash sequence is infinite");
+        let block = &self.blocks[Block::select(first, self.blocks.len())];
+
+        hashes.take(self.hashes).all(|hash| block.get(hash))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`BlockedBloomFilter`], with the words of the blocks in little-endian order.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        blocks: usize,
+        hashes: usize,
+        bits: Bytes,
+    }
+
+    impl<T, B> Serialize for BlockedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let words = self.blocks.iter().flat_map(|block| block.0);
+
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                blocks: self.blocks.len(),
+                hashes: self.hashes,
+                bits: Bytes::from_values(words, u64::BITS),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for BlockedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> BlockedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            let words = repr
+                .bits
+                .into_values(repr.blocks.saturating_mul(BLOCK_BITS / 64), u64::BITS)?;
+            let mut filter = Self::new(repr.blocks, repr.hashes, builder)?;
+
+            filter
+                .blocks
+                .iter_mut()
+                .zip(words.chunks_exact(BLOCK_BITS / 64))
+                .for_each(|(block, words)| block.0.copy_from_slice(words));
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn insert_contains() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter =
+            BlockedBloomFilter::<u32, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+
+        (0..1000u32).for_each(|i| filter.insert(&i));
+        assert!((0..1000u32).all(|i| filter.contains(&i)));
+
+        let false_positives = (1000..11_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 150);
+    }
+
+    #[test]
+    fn with_capacity_and_fpr() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter =
+            BlockedBloomFilter::<u32, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+
+        assert!(filter.bit_len() >= params::optimal_bits(1000, 0.01));
+        assert_eq!(filter.hashes(), 7);
+    }
+
+    #[test]
+    fn power_of_two_blocks() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = BlockedBloomFilter::<u32, _>::new(512, 8, builder).unwrap();
+
+        // About 12 bits for each item, where the rate of the blocked layout is below 0.6%.
+        (0..20_000u32).for_each(|i| filter.insert(&i));
+        let false_positives = (20_000..120_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 1000);
+    }
+}
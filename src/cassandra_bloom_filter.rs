@@ -0,0 +1,292 @@
+use crate::{murmur3::cassandra_murmur3_x64_128, Error};
+use std::io::Read;
+
+/// The layouts of the `-Filter.db` component, which changed with the sstable versions of Cassandra.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CassandraFilterFormat {
+    /// The `big` sstables before version `ma` (Cassandra 2.x), which swap the two halves of the hash value
+    /// and store the bits as big-endian longs.
+    Cassandra2,
+    /// The `big` sstables from version `ma` to `md` (Cassandra 3.x), which store the bits as big-endian longs.
+    Cassandra3,
+    /// The `big` sstables from version `na` (Cassandra 4.0 and later) and the `bti` sstables, which store the
+    /// bits as they are laid out in memory.
+    Cassandra4,
+}
+
+impl CassandraFilterFormat {
+    /// Returns the format of the filter of a `big` sstable with a given version, the two letters which prefix
+    /// the name of its components, like `nb` in `nb-1-big-Filter.db`.
+    pub fn from_big_version(version: &str) -> Result<Self, Error> {
+        if version.len() != 2 || !version.bytes().all(|c| c.is_ascii_lowercase()) {
+            return Err(Error::InvalidFormat("sstable version"));
+        }
+
+        let format = if version < "ma" {
+            Self::Cassandra2
+        } else if version < "na" {
+            Self::Cassandra3
+        } else {
+            Self::Cassandra4
+        };
+
+        Ok(format)
+    }
+}
+
+/// Reads the bloom filters serialized by [Cassandra](https://cassandra.apache.org) in the `-Filter.db` component
+/// of its sstables, so the partition keys can be tested against the filter of a table without the database.
+/// The filter is read-only, and answers like `BloomFilter.isPresent` does.
+///
+/// The keys are the serialized partition keys, hashed with Cassandra's MurmurHash3, which differs from
+/// the reference one for some keys. The layout of the bits depends on the version of the sstable,
+/// see [`CassandraFilterFormat`].
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::{CassandraBloomFilter, CassandraFilterFormat};
+///
+/// // A filter with 5 hash values and a single long, where all the bits are set.
+/// let mut bytes = vec![0, 0, 0, 5, 0, 0, 0, 1];
+/// bytes.extend_from_slice(&[0xff; 8]);
+///
+/// let format = CassandraFilterFormat::from_big_version("nb").unwrap();
+/// let filter = CassandraBloomFilter::from_bytes(&bytes, format).unwrap();
+/// assert_eq!(filter.bit_len(), 64);
+/// assert!(filter.contains("Hello world!"));
+///```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CassandraBloomFilter {
+    bits: Vec<u8>,
+    hashes: usize,
+    old_hash_order: bool,
+}
+
+impl CassandraBloomFilter {
+    /// Reads a filter from the bytes of a `-Filter.db` component with a given format: the number of hash
+    /// values and the number of longs as big-endian `int`s, followed by the bits. It fails when the bytes
+    /// are not a valid serialized filter.
+    pub fn from_bytes(bytes: &[u8], format: CassandraFilterFormat) -> Result<Self, Error> {
+        let mut reader = bytes;
+        let filter = Self::read_from(&mut reader, format)?;
+
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat("trailing bytes"));
+        }
+
+        Ok(filter)
+    }
+
+    /// Reads a filter from a reader, see [`CassandraBloomFilter::from_bytes`].
+    pub fn read_from<R: Read>(mut reader: R, format: CassandraFilterFormat) -> Result<Self, Error> {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+
+        let hashes = i32::from_be_bytes(header[..4].try_into().unwrap());
+        if hashes <= 0 {
+            return Err(Error::InvalidFormat("number of hashes"));
+        }
+
+        let longs = i32::from_be_bytes(header[4..].try_into().unwrap());
+        if longs <= 0 {
+            return Err(Error::InvalidFormat("bit array length"));
+        }
+
+        // The bits are read as they arrive, so a corrupted length does not allocate the whole array up front.
+        let len = longs as u64 * 8;
+        let mut bits = Vec::new();
+        reader.take(len).read_to_end(&mut bits)?;
+        if bits.len() as u64 != len {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof));
+        }
+
+        // The big-endian longs hold the bytes of the memory in little-endian order.
+        if format != CassandraFilterFormat::Cassandra4 {
+            bits.chunks_exact_mut(8).for_each(|long| long.reverse());
+        }
+
+        Ok(Self {
+            bits,
+            hashes: hashes as usize,
+            old_hash_order: format == CassandraFilterFormat::Cassandra2,
+        })
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn bit_len(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Returns the number of hash values computed for each key.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Checks if a given partition key may be present in the sstable.
+    pub fn contains<K: AsRef<[u8]> + ?Sized>(&self, key: &K) -> bool {
+        let (h1, h2) = cassandra_murmur3_x64_128(key.as_ref(), 0);
+        let (mut base, inc) = if self.old_hash_order {
+            (h1, h2)
+        } else {
+            (h2, h1)
+        };
+
+        let len = self.bit_len() as i64;
+        (0..self.hashes).all(|_| {
+            // Java's remainder keeps the sign of the dividend, and Cassandra takes its absolute value.
+            let index = (base as i64 % len).unsigned_abs() as usize;
+            base = base.wrapping_add(inc);
+            self.bits[index >> 3] & (1 << (index & 7)) != 0
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`CassandraBloomFilter`], with its bits laid out like in memory.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hashes: usize,
+        old_hash_order: bool,
+        bits: Bytes,
+    }
+
+    impl Serialize for CassandraBloomFilter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hashes: self.hashes,
+                old_hash_order: self.old_hash_order,
+                bits: Bytes(self.bits.clone()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CassandraBloomFilter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+
+            if repr.hashes == 0 {
+                return Err(de_error(Error::InvalidFormat("number of hashes")));
+            }
+
+            if repr.bits.0.is_empty() || !repr.bits.0.len().is_multiple_of(8) {
+                return Err(de_error(Error::InvalidFormat("bit array length")));
+            }
+
+            Ok(Self {
+                bits: repr.bits.0,
+                hashes: repr.hashes,
+                old_hash_order: repr.old_hash_order,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bits set by the hashing code of Cassandra 3.0 and later in a filter of 256 bits, with 5 hash values,
+    /// for the keys `key0` to `key9`.
+    const BITS: [usize; 42] = [
+        2, 6, 10, 15, 16, 17, 21, 22, 26, 28, 34, 41, 54, 56, 58, 63, 72, 74, 76, 81, 84, 85, 86,
+        94, 107, 118, 119, 122, 126, 130, 142, 146, 169, 179, 196, 199, 206, 210, 218, 242, 250,
+        254,
+    ];
+
+    /// The bits set by Cassandra 2.x, with the old hash order, for the same keys.
+    const OLD_BITS: [usize; 47] = [
+        7, 10, 14, 28, 31, 35, 41, 48, 49, 50, 52, 54, 56, 58, 59, 70, 72, 74, 76, 78, 92, 94, 100,
+        102, 107, 112, 116, 122, 132, 134, 139, 164, 176, 179, 184, 192, 194, 196, 201, 202, 206,
+        212, 234, 240, 242, 249, 254,
+    ];
+
+    /// Serializes a filter of 256 bits and 5 hash values like Cassandra 4.0 and later.
+    fn serialize(bits: &[usize]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0, 5, 0, 0, 0, 4];
+        bytes.resize(8 + 32, 0);
+        bits.iter()
+            .for_each(|bit| bytes[8 + bit / 8] |= 1 << (bit % 8));
+        bytes
+    }
+
+    /// Converts the bits serialized by [`serialize`] to big-endian longs.
+    fn to_longs(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes[8..]
+            .chunks_exact_mut(8)
+            .for_each(|long| long.reverse());
+        bytes
+    }
+
+    fn check(filter: &CassandraBloomFilter) {
+        assert_eq!(filter.bit_len(), 256);
+        assert_eq!(filter.hashes(), 5);
+        assert!((0..10).all(|i| filter.contains(&format!("key{i}"))));
+        assert!((10..200).all(|i| !filter.contains(&format!("key{i}"))));
+    }
+
+    #[test]
+    fn formats() {
+        let format = |version| CassandraFilterFormat::from_big_version(version).unwrap();
+        assert_eq!(format("jb"), CassandraFilterFormat::Cassandra2);
+        assert_eq!(format("lb"), CassandraFilterFormat::Cassandra2);
+        assert_eq!(format("ma"), CassandraFilterFormat::Cassandra3);
+        assert_eq!(format("md"), CassandraFilterFormat::Cassandra3);
+        assert_eq!(format("na"), CassandraFilterFormat::Cassandra4);
+        assert_eq!(format("oa"), CassandraFilterFormat::Cassandra4);
+
+        let res = CassandraFilterFormat::from_big_version("big");
+        assert_eq!(res, Err(Error::InvalidFormat("sstable version")));
+    }
+
+    #[test]
+    fn golden_filters() {
+        let bytes = serialize(&BITS);
+        check(
+            &CassandraBloomFilter::from_bytes(&bytes, CassandraFilterFormat::Cassandra4).unwrap(),
+        );
+
+        let bytes = to_longs(serialize(&BITS));
+        check(
+            &CassandraBloomFilter::from_bytes(&bytes, CassandraFilterFormat::Cassandra3).unwrap(),
+        );
+
+        let bytes = to_longs(serialize(&OLD_BITS));
+        check(
+            &CassandraBloomFilter::from_bytes(&bytes, CassandraFilterFormat::Cassandra2).unwrap(),
+        );
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let read = |bytes: &[u8]| {
+            CassandraBloomFilter::from_bytes(bytes, CassandraFilterFormat::Cassandra4)
+        };
+
+        let mut bytes = serialize(&BITS);
+        assert!(read(&bytes).is_ok());
+
+        assert_eq!(
+            read(&bytes[..20]),
+            Err(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+
+        bytes.push(0);
+        assert_eq!(read(&bytes), Err(Error::InvalidFormat("trailing bytes")));
+
+        assert_eq!(
+            read(&[0, 0, 0, 0, 0, 0, 0, 1]),
+            Err(Error::InvalidFormat("number of hashes"))
+        );
+        assert_eq!(
+            read(&[0, 0, 0, 5, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::InvalidFormat("bit array length"))
+        );
+    }
+}
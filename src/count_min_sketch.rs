@@ -0,0 +1,366 @@
+use crate::{bit_index, builder_fingerprint, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use std::{
+    borrow::Borrow,
+    f64::consts::E,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Implements the [count-min sketch](https://doi.org/10.1016/j.jalgor.2003.12.001) of Cormode and Muthukrishnan,
+/// which estimates how many times each item was added. The counters are arranged in `depth` rows of `width`
+/// counters, and the i-th hash value of an item selects its counter in the i-th row. An estimate is the minimum
+/// of the counters of the item, so it is never lower than the true count.
+///
+/// With `width = e / epsilon` and `depth = ln(1 / delta)`, an estimate exceeds the true count by more than
+/// `epsilon` times the total count with a probability of at most `delta`.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::CountMinSketch;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut sketch = CountMinSketch::<str, _>::with_error_rates(0.001, 0.01, builder).unwrap();
+///
+/// sketch.add("Hello", 3);
+/// sketch.add("world!", 1);
+/// sketch.add("Hello", 2);
+///
+/// assert_eq!(sketch.estimate("Hello"), 5);
+/// assert_eq!(sketch.total(), 6);
+///```
+pub struct CountMinSketch<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    counters: Vec<u64>,
+    width: usize,
+    depth: usize,
+    total: u64,
+    conservative: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> CountMinSketch<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`CountMinSketch`] instance with `depth` rows of `width` counters.
+    pub fn new(width: usize, depth: usize, builder: B) -> Result<Self, Error> {
+        if width == 0 {
+            return Err(Error::ZeroSize("width"));
+        }
+
+        if depth == 0 {
+            return Err(Error::ZeroSize("depth"));
+        }
+
+        Ok(Self {
+            builder,
+            counters: vec![0; width * depth],
+            width,
+            depth,
+            total: 0,
+            conservative: false,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`CountMinSketch`] instance where an estimate exceeds the true count by more than
+    /// `epsilon` times the total count with a probability of at most `delta`.
+    pub fn with_error_rates(epsilon: f64, delta: f64, builder: B) -> Result<Self, Error> {
+        if !(epsilon > 0.0 && epsilon < 1.0) {
+            return Err(Error::InvalidProbability(epsilon));
+        }
+
+        if !(delta > 0.0 && delta < 1.0) {
+            return Err(Error::InvalidProbability(delta));
+        }
+
+        let width = (E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        Self::new(width, depth, builder)
+    }
+
+    /// Enables or disables the conservative update, which only increments the counters of an item which are
+    /// lower than its new estimate. It reduces the overestimation, but the counts can no longer be decremented.
+    pub fn with_conservative_update(mut self, conservative: bool) -> Self {
+        self.conservative = conservative;
+        self
+    }
+
+    /// Returns the number of counters in each row.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows, which is also the number of hash values computed for each item.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns true when the sketch uses the conservative update.
+    pub fn is_conservative(&self) -> bool {
+        self.conservative
+    }
+
+    /// Returns the sum of the counts added to the sketch.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the error `e / width` of the estimates, relative to the total count.
+    pub fn epsilon(&self) -> f64 {
+        E / self.width as f64
+    }
+
+    /// Returns the probability `e^-depth` that an estimate exceeds the error bound.
+    pub fn delta(&self) -> f64 {
+        (-(self.depth as f64)).exp()
+    }
+
+    /// Adds to the sketch the counts of another sketch. It fails when the sketches have different sizes,
+    /// when only one of them uses conservative update or when they are built with different hasher builders.
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        if self.width != other.width || self.depth != other.depth {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if self.conservative != other.conservative {
+            return Err(Error::IncompatibleUpdates);
+        }
+
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        self.counters
+            .iter_mut()
+            .zip(&other.counters)
+            .for_each(|(x, y)| *x = x.saturating_add(*y));
+        self.total = self.total.saturating_add(other.total);
+
+        Ok(())
+    }
+}
+
+impl<T, B> CountMinSketch<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Adds `count` occurrences of an item to the sketch.
+    pub fn add<U>(&mut self, item: &U, count: u64)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.total = self.total.saturating_add(count);
+
+        let indexes = self.indexes(item);
+        if self.conservative {
+            let estimate = indexes.iter().map(|index| self.counters[*index]).min();
+            let target = estimate.unwrap_or_default().saturating_add(count);
+
+            indexes
+                .into_iter()
+                .for_each(|index| self.counters[index] = self.counters[index].max(target));
+        } else {
+            indexes.into_iter().for_each(|index| {
+                self.counters[index] = self.counters[index].saturating_add(count)
+            });
+        }
+    }
+
+    /// Estimates how many times an item was added to the sketch. The estimate is never lower than the true count.
+    pub fn estimate<U>(&self, item: &U) -> u64
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.indexes(item)
+            .into_iter()
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn indexes<U>(&self, item: &U) -> Vec<usize>
+    where
+        U: Hash + ?Sized,
+    {
+        let width = self.width;
+
+        self.builder
+            .hashes_one(item)
+            .take(self.depth)
+            .enumerate()
+            .map(|(row, hash)| row * width + bit_index(hash, width))
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`CountMinSketch`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        width: usize,
+        depth: usize,
+        total: u64,
+        conservative: bool,
+        counters: Vec<u64>,
+    }
+
+    impl<T, B> Serialize for CountMinSketch<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                width: self.width,
+                depth: self.depth,
+                total: self.total,
+                conservative: self.conservative,
+                counters: self.counters.clone(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for CountMinSketch<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> CountMinSketch<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a sketch with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the sketch was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            if repr.counters.len() != repr.width.saturating_mul(repr.depth) {
+                return Err(Error::InvalidFormat("number of counters"));
+            }
+
+            let mut sketch = Self::new(repr.width, repr.depth, builder)?
+                .with_conservative_update(repr.conservative);
+            sketch.counters = repr.counters;
+            sketch.total = repr.total;
+
+            Ok(sketch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn error_bound() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut sketch = CountMinSketch::<u32, _>::with_error_rates(0.01, 0.01, builder).unwrap();
+
+        assert_eq!(sketch.width(), 272);
+        assert_eq!(sketch.depth(), 5);
+
+        (0..1000u32).for_each(|i| sketch.add(&i, (i % 10) as u64 + 1));
+
+        let bound = (sketch.epsilon() * sketch.total() as f64) as u64;
+        let within = (0..1000u32)
+            .filter(|i| {
+                let count = (i % 10) as u64 + 1;
+                let estimate = sketch.estimate(i);
+                estimate >= count && estimate <= count + bound
+            })
+            .count();
+
+        assert!(within >= 990);
+    }
+
+    #[test]
+    fn conservative_update() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut standard = CountMinSketch::<u32, _>::new(100, 4, builder()).unwrap();
+        let mut conservative = CountMinSketch::<u32, _>::new(100, 4, builder())
+            .unwrap()
+            .with_conservative_update(true);
+
+        (0..1000u32).for_each(|i| {
+            standard.add(&i, 1);
+            conservative.add(&i, 1);
+        });
+
+        assert!((0..1000u32).all(|i| conservative.estimate(&i) >= 1));
+        assert!((0..1000u32).all(|i| conservative.estimate(&i) <= standard.estimate(&i)));
+        assert!(conservative.counters.iter().sum::<u64>() < standard.counters.iter().sum::<u64>());
+    }
+
+    #[test]
+    fn merge() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut sketch1 = CountMinSketch::<str, _>::new(100, 4, builder()).unwrap();
+        let mut sketch2 = CountMinSketch::<str, _>::new(100, 4, builder()).unwrap();
+
+        sketch1.add("Hello", 2);
+        sketch2.add("Hello", 3);
+        sketch2.add("world!", 1);
+
+        sketch1.merge(&sketch2).unwrap();
+        assert_eq!(sketch1.estimate("Hello"), 5);
+        assert_eq!(sketch1.total(), 6);
+
+        let other = CountMinSketch::<str, _>::new(50, 4, builder()).unwrap();
+        assert_eq!(sketch1.merge(&other), Err(Error::IncompatibleSizes));
+
+        let other = CountMinSketch::<str, _>::new(100, 4, builder())
+            .unwrap()
+            .with_conservative_update(true);
+        assert_eq!(sketch1.merge(&other), Err(Error::IncompatibleUpdates));
+
+        let other =
+            CountMinSketch::<str, _>::new(100, 4, BuildPairHasher::new_with_keys((2, 2), (3, 3)));
+        assert_eq!(
+            sketch1.merge(&other.unwrap()),
+            Err(Error::IncompatibleHashers)
+        );
+    }
+}
@@ -0,0 +1,142 @@
+/// A fixed length array of small saturating counters, used by [`crate::CountingBloomFilter`]
+/// and [`crate::StableBloomFilter`].
+pub trait Counters {
+    /// The maximum value of a counter.
+    const MAX: u16;
+
+    /// Creates a new array with `len` counters set to zero.
+    fn with_len(len: usize) -> Self;
+
+    /// Returns the number of counters.
+    fn len(&self) -> usize;
+
+    /// Returns true when the array has no counters.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of the counter at a given index.
+    fn get(&self, index: usize) -> u16;
+
+    /// Sets the value of the counter at a given index. The value must not be greater than [`Counters::MAX`].
+    fn set(&mut self, index: usize, value: u16);
+
+    /// Increments the counter at a given index, unless it is saturated.
+    fn increment(&mut self, index: usize) {
+        let value = self.get(index);
+        if value < Self::MAX {
+            self.set(index, value + 1);
+        }
+    }
+
+    /// Decrements the counter at a given index, unless it is zero.
+    fn decrement(&mut self, index: usize) {
+        let value = self.get(index);
+        if value > 0 {
+            self.set(index, value - 1);
+        }
+    }
+}
+
+/// An array of 4-bit counters, two counters packed in each byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counters4 {
+    cells: Vec<u8>,
+    len: usize,
+}
+
+impl Counters for Counters4 {
+    const MAX: u16 = 0xF;
+
+    fn with_len(len: usize) -> Self {
+        Self {
+            cells: vec![0; len.div_ceil(2)],
+            len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> u16 {
+        let shift = (index % 2) * 4;
+        ((self.cells[index / 2] >> shift) & 0xF) as u16
+    }
+
+    fn set(&mut self, index: usize, value: u16) {
+        let shift = (index % 2) * 4;
+        let cell = &mut self.cells[index / 2];
+        *cell = (*cell & !(0xF << shift)) | ((value as u8 & 0xF) << shift);
+    }
+}
+
+/// An array of 8-bit counters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counters8(Vec<u8>);
+
+impl Counters for Counters8 {
+    const MAX: u16 = u8::MAX as u16;
+
+    fn with_len(len: usize) -> Self {
+        Self(vec![0; len])
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, index: usize) -> u16 {
+        self.0[index] as u16
+    }
+
+    fn set(&mut self, index: usize, value: u16) {
+        self.0[index] = value as u8;
+    }
+}
+
+/// An array of 16-bit counters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counters16(Vec<u16>);
+
+impl Counters for Counters16 {
+    const MAX: u16 = u16::MAX;
+
+    fn with_len(len: usize) -> Self {
+        Self(vec![0; len])
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, index: usize) -> u16 {
+        self.0[index]
+    }
+
+    fn set(&mut self, index: usize, value: u16) {
+        self.0[index] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters4() {
+        let mut counters = Counters4::with_len(3);
+        (0..20).for_each(|_| counters.increment(1));
+        counters.increment(2);
+
+        assert_eq!(counters.len(), 3);
+        assert_eq!(counters.get(0), 0);
+        assert_eq!(counters.get(1), Counters4::MAX);
+        assert_eq!(counters.get(2), 1);
+
+        counters.decrement(1);
+        counters.decrement(0);
+        assert_eq!(counters.get(0), 0);
+        assert_eq!(counters.get(1), Counters4::MAX - 1);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{bit_index, params, BloomFilter, Counters, Counters4, Error, VecBloomFilter};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::vec::BitVec;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Implements the [counting bloom filter](https://en.wikipedia.org/wiki/Counting_Bloom_filter), which replaces
+/// each bit of a bloom filter with a small saturating counter, so the items can also be removed.
+/// The [`C`] generic argument is the storage of the counters: [`Counters4`], [`crate::Counters8`] or [`crate::Counters16`].
+/// Removing an item is safe only when it was actually inserted: removing a false positive decrements the counters
+/// of other items, which can then be reported as absent. A saturated counter is never decremented, since it may
+/// have missed some increments.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::*;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = CountingBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+///
+/// filter.insert("Hello world!");
+/// assert!(filter.contains("Hello world!"));
+///
+/// assert!(filter.remove("Hello world!"));
+/// assert!(!filter.contains("Hello world!"));
+///```
+pub struct CountingBloomFilter<T, B, C = Counters4>
+where
+    T: ?Sized,
+{
+    builder: B,
+    counters: C,
+    hashes: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B, C> CountingBloomFilter<T, B, C>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    C: Counters,
+{
+    /// Creates a new [`CountingBloomFilter`] instance with a given number of counters and hash values for each item.
+    pub fn new(counters: usize, hashes: usize, builder: B) -> Result<Self, Error> {
+        if counters == 0 {
+            return Err(Error::ZeroSize("number of counters"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        Ok(Self {
+            builder,
+            counters: C::with_len(counters),
+            hashes,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`CountingBloomFilter`] instance sized to hold `items` items while keeping
+    /// the false-positive probability under `fpr`, with a counter for each bit of the [`VecBloomFilter`]
+    /// of [`VecBloomFilter::with_capacity_and_fpr`].
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        let solution = params::Params::new().items(items).fpr(fpr).solve()?;
+        Self::new(solution.bits, solution.hashes, builder)
+    }
+
+    /// Returns the number of counters in the filter.
+    pub fn counters_len(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Returns the number of hash values computed for each item.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    fn into_bits(self) -> (BitVec, usize, B) {
+        let bits = (0..self.counters.len())
+            .map(|index| self.counters.get(index) > 0)
+            .collect();
+
+        (bits, self.hashes, self.builder)
+    }
+}
+
+impl<T, B, C> CountingBloomFilter<T, B, C>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+    C: Counters,
+{
+    /// Inserts in the filter a new item.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let len = self.counters.len();
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .for_each(|hash| self.counters.increment(bit_index(hash, len)));
+    }
+
+    /// Removes an item from the filter. It returns false, without changing the filter,
+    /// when the item is not present in the filter.
+    pub fn remove<U>(&mut self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        if !self.contains(item) {
+            return false;
+        }
+
+        let len = self.counters.len();
+        let decrement = |hash| {
+            let index = bit_index(hash, len);
+            if self.counters.get(index) < C::MAX {
+                self.counters.decrement(index);
+            }
+        };
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .for_each(decrement);
+
+        true
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.count_estimate(item) > 0
+    }
+
+    /// Estimates how many times an item was inserted in the filter, as the minimum of its counters.
+    /// The estimate is never lower than the real count, unless the counters are saturated.
+    pub fn count_estimate<U>(&self, item: &U) -> u16
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let len = self.counters.len();
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .map(|hash| self.counters.get(bit_index(hash, len)))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+impl<T, B, C> From<CountingBloomFilter<T, B, C>> for VecBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    C: Counters,
+{
+    fn from(filter: CountingBloomFilter<T, B, C>) -> Self {
+        let (bits, hashes, builder) = filter.into_bits();
+        VecBloomFilter::from_bits(bits, hashes, builder)
+    }
+}
+
+impl<T, B, C, const K: usize, const H: usize> TryFrom<CountingBloomFilter<T, B, C>>
+    for BloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    C: Counters,
+{
+    type Error = Error;
+
+    /// Converts the counting filter into a [`BloomFilter`]. It fails when the number of counters is
+    /// not the number of bits of the [`BloomFilter`] or the number of hash values is not `H`.
+    fn try_from(filter: CountingBloomFilter<T, B, C>) -> Result<Self, Self::Error> {
+        let mut bloom = BloomFilter::new(filter.builder);
+        if filter.counters.len() != bloom.bits.len() || filter.hashes != H {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        (0..filter.counters.len())
+            .filter(|index| filter.counters.get(*index) > 0)
+            .for_each(|index| bloom.bits.set(index, true));
+
+        Ok(bloom)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, check_size, counter_bits, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`CountingBloomFilter`]. The width of the counters rejects a filter
+    /// deserialized with a different storage of the counters.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        counter_bits: u32,
+        len: usize,
+        hashes: usize,
+        counters: Bytes,
+    }
+
+    impl<T, B, C> Serialize for CountingBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                counter_bits: counter_bits::<C>(),
+                len: self.counters.len(),
+                hashes: self.hashes,
+                counters: Bytes::from_counters(&self.counters),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B, C> Deserialize<'de> for CountingBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B, C> CountingBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the counters have a different width or when the filter was built with a different
+        /// hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_size(repr.counter_bits, counter_bits::<C>())?;
+            check_hasher(repr.hasher, &builder)?;
+
+            let counters = repr.counters.into_counters(repr.len)?;
+            let mut filter = Self::new(repr.len, repr.hashes, builder)?;
+            filter.counters = counters;
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Counters16, Counters8};
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn insert_remove() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CountingBloomFilter::<u32, _, Counters8>::new(1000, 4, builder).unwrap();
+
+        (0..100u32).for_each(|i| filter.insert(&i));
+        filter.insert(&7);
+
+        assert_eq!(filter.count_estimate(&7), 2);
+        assert!((0..50u32).all(|i| filter.remove(&i)));
+        assert!((50..100u32).all(|i| filter.contains(&i)));
+        assert!(filter.contains(&7));
+    }
+
+    #[test]
+    fn with_capacity_and_fpr() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter =
+            CountingBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+
+        assert_eq!(filter.counters_len(), 9593);
+        assert_eq!(filter.hashes(), 7);
+    }
+
+    #[test]
+    fn saturated_counters() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CountingBloomFilter::<u32, _>::new(100, 3, builder).unwrap();
+
+        (0..20).for_each(|_| filter.insert(&1));
+        (0..20).for_each(|_| {
+            filter.remove(&1);
+        });
+
+        assert_eq!(filter.count_estimate(&1), Counters4::MAX);
+    }
+
+    #[test]
+    fn into_bloom_filter() {
+        let bits = 10 * u64::BITS as usize;
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CountingBloomFilter::<u32, _, Counters16>::new(bits, 3, builder).unwrap();
+        (0..10u32).for_each(|i| filter.insert(&i));
+
+        let mut bloom: BloomFilter<u32, _, 10, 3> = filter.try_into().unwrap();
+        assert!((0..10u32).all(|i| bloom.contains(&i)));
+
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = CountingBloomFilter::<u32, _, Counters16>::new(bits, 3, builder).unwrap();
+        let res: Result<BloomFilter<u32, _, 10, 4>, _> = filter.try_into();
+        assert_eq!(res.err(), Some(Error::IncompatibleSizes));
+
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CountingBloomFilter::<u32, _>::new(100, 3, builder).unwrap();
+        filter.insert(&1);
+
+        let bloom = VecBloomFilter::from(filter);
+        assert!(bloom.contains(&1));
+        assert_eq!(bloom.bit_len(), 100);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{builder_fingerprint, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The default number of fingerprints in a bucket.
+pub const DEFAULT_BUCKET_SIZE: usize = 4;
+
+/// The maximum number of fingerprints relocated by an insertion before the filter is considered full.
+const MAX_KICKS: usize = 500;
+
+/// The maximum load factor of the buckets when the filter holds its capacity.
+const MAX_LOAD_FACTOR: f64 = 0.95;
+
+/// Implements the [cuckoo filter](https://doi.org/10.1145/2674005.2674994) of Fan et al.
+/// Each item is stored as a short fingerprint in one of two buckets. Both the fingerprint and the first bucket
+/// are derived from the first hash value of the item, while the second bucket is the first one xor-ed with the
+/// hash value of the fingerprint, so a fingerprint can be moved between its buckets without knowing the item.
+///
+/// For false-positive rates below 3%, it uses less space than a bloom filter, and it supports removing items.
+/// Inserting the same item more than twice the bucket size fills its buckets, so the filter is better suited
+/// to sets than to multisets.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::CuckooFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = CuckooFilter::<str, _>::with_capacity_and_fpr(1000, 0.001, builder).unwrap();
+///
+/// filter.insert("Hello world!").unwrap();
+/// assert!(filter.contains("Hello world!"));
+///
+/// assert!(filter.remove("Hello world!"));
+/// assert!(!filter.contains("Hello world!"));
+///```
+pub struct CuckooFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    slots: Vec<u32>,
+    buckets: usize,
+    bucket_size: usize,
+    fingerprint_bits: u32,
+    len: usize,
+    rng: ChaCha12Rng,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> CuckooFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`CuckooFilter`] instance which can hold `capacity` items, with fingerprints of
+    /// `fingerprint_bits` bits (up to 32) and buckets of `bucket_size` fingerprints.
+    pub fn new(
+        capacity: usize,
+        fingerprint_bits: u32,
+        bucket_size: usize,
+        builder: B,
+    ) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(Error::ZeroSize("capacity"));
+        }
+
+        if bucket_size == 0 {
+            return Err(Error::ZeroSize("bucket size"));
+        }
+
+        if fingerprint_bits == 0 || fingerprint_bits > u32::BITS {
+            return Err(Error::InvalidFingerprintBits(fingerprint_bits));
+        }
+
+        let mut buckets = capacity.div_ceil(bucket_size).next_power_of_two();
+        if capacity as f64 / (buckets * bucket_size) as f64 > MAX_LOAD_FACTOR {
+            buckets *= 2;
+        }
+
+        let rng = ChaCha12Rng::seed_from_u64(builder_fingerprint(&builder));
+
+        Ok(Self {
+            builder,
+            slots: vec![0; buckets * bucket_size],
+            buckets,
+            bucket_size,
+            fingerprint_bits,
+            len: 0,
+            rng,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`CuckooFilter`] instance which can hold `capacity` items, with buckets of
+    /// [`DEFAULT_BUCKET_SIZE`] fingerprints and fingerprints long enough to keep the false-positive
+    /// probability under `fpr`.
+    pub fn with_capacity_and_fpr(capacity: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        // An absent item is compared with at most 2b fingerprints, each matching with probability 2^-f.
+        let bits = (2.0 * DEFAULT_BUCKET_SIZE as f64 / fpr).log2().ceil() as u32;
+        Self::new(capacity, bits, DEFAULT_BUCKET_SIZE, builder)
+    }
+
+    /// Returns the number of items in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the filter has no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of fingerprints the filter can store.
+    pub fn slots(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of bits of a fingerprint.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    /// Returns the fraction of the slots which store a fingerprint.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots.len() as f64
+    }
+
+    /// Returns the upper bound of the false-positive probability `1 - (1 - 2^-f)^2b`.
+    pub fn false_positive_rate(&self) -> f64 {
+        let miss = 1.0 - 0.5f64.powi(self.fingerprint_bits as i32);
+        1.0 - miss.powi(2 * self.bucket_size as i32)
+    }
+
+    fn bucket(&self, index: usize) -> &[u32] {
+        &self.slots[index * self.bucket_size..(index + 1) * self.bucket_size]
+    }
+
+    fn bucket_mut(&mut self, index: usize) -> &mut [u32] {
+        &mut self.slots[index * self.bucket_size..(index + 1) * self.bucket_size]
+    }
+
+    fn alt_index(&self, index: usize, fingerprint: u32) -> usize {
+        let hash = self.builder.hash_one(fingerprint) as usize;
+        (index ^ hash) & (self.buckets - 1)
+    }
+
+    fn put(&mut self, index: usize, fingerprint: u32) -> bool {
+        match self.bucket_mut(index).iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn relocate(&mut self, index: usize, fingerprint: u32) -> Result<(), Error> {
+        let mut path = Vec::new();
+        let mut index = index;
+        let mut fingerprint = fingerprint;
+
+        for _ in 0..MAX_KICKS {
+            let slot = self.rng.gen_range(0..self.bucket_size);
+            std::mem::swap(&mut fingerprint, &mut self.bucket_mut(index)[slot]);
+            path.push((index, slot));
+
+            index = self.alt_index(index, fingerprint);
+            if self.put(index, fingerprint) {
+                return Ok(());
+            }
+        }
+
+        // Undo the relocations, so a failed insertion leaves the filter unchanged.
+        for (index, slot) in path.into_iter().rev() {
+            std::mem::swap(&mut fingerprint, &mut self.bucket_mut(index)[slot]);
+        }
+
+        Err(Error::Full)
+    }
+}
+
+impl<T, B> CuckooFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item. It returns [`Error::Full`], leaving the filter unchanged,
+    /// when there is no room for the fingerprint of the item.
+    pub fn insert<U>(&mut self, item: &U) -> Result<(), Error>
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let (fingerprint, index1) = self.fingerprint_index(item);
+        let index2 = self.alt_index(index1, fingerprint);
+
+        if !self.put(index1, fingerprint) && !self.put(index2, fingerprint) {
+            let index = if self.rng.gen() { index1 } else { index2 };
+            self.relocate(index, fingerprint)?;
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes an item from the filter. It returns false when the item is not present in the filter.
+    /// Removing an item which was not inserted may remove another item with the same fingerprint.
+    pub fn remove<U>(&mut self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let (fingerprint, index1) = self.fingerprint_index(item);
+        let index2 = self.alt_index(index1, fingerprint);
+
+        for index in [index1, index2] {
+            if let Some(slot) = self
+                .bucket_mut(index)
+                .iter_mut()
+                .find(|slot| **slot == fingerprint)
+            {
+                *slot = 0;
+                self.len -= 1;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let (fingerprint, index1) = self.fingerprint_index(item);
+        let index2 = self.alt_index(index1, fingerprint);
+
+        self.bucket(index1).contains(&fingerprint) || self.bucket(index2).contains(&fingerprint)
+    }
+
+    fn fingerprint_index<U>(&self, item: &U) -> (u32, usize)
+    where
+        U: Hash + ?Sized,
+    {
+        let hash: u64 = self
+            .builder
+            .hashes_one(item)
+            .next()
+            .expect("the hash sequence is infinite")
+            .into();
+
+        // The zero fingerprint marks an empty slot.
+        let fingerprint = ((hash >> 32) as u32) >> (u32::BITS - self.fingerprint_bits);
+        let fingerprint = fingerprint.max(1);
+        let index = hash as usize & (self.buckets - 1);
+
+        (fingerprint, index)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`CuckooFilter`], with `fingerprint_bits` bits for each slot. The random generator
+    /// is seeded from the hasher builder, so its position is enough to restore it.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        buckets: usize,
+        bucket_size: usize,
+        fingerprint_bits: u32,
+        rng_word_pos: u128,
+        slots: Bytes,
+    }
+
+    impl<T, B> Serialize for CuckooFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let slots = self.slots.iter().map(|slot| *slot as u64);
+
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                buckets: self.buckets,
+                bucket_size: self.bucket_size,
+                fingerprint_bits: self.fingerprint_bits,
+                rng_word_pos: self.rng.get_word_pos(),
+                slots: Bytes::from_values(slots, self.fingerprint_bits),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for CuckooFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> CuckooFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            if repr.bucket_size == 0 {
+                return Err(Error::ZeroSize("bucket size"));
+            }
+
+            if !repr.buckets.is_power_of_two() {
+                return Err(Error::InvalidFormat("number of buckets"));
+            }
+
+            if repr.fingerprint_bits == 0 || repr.fingerprint_bits > u32::BITS {
+                return Err(Error::InvalidFingerprintBits(repr.fingerprint_bits));
+            }
+
+            let slots = repr.slots.into_values(
+                repr.buckets.saturating_mul(repr.bucket_size),
+                repr.fingerprint_bits,
+            )?;
+
+            let mut rng = ChaCha12Rng::seed_from_u64(builder_fingerprint(&builder));
+            rng.set_word_pos(repr.rng_word_pos);
+
+            Ok(Self {
+                builder,
+                len: slots.iter().filter(|slot| **slot != 0).count(),
+                slots: slots.into_iter().map(|slot| slot as u32).collect(),
+                buckets: repr.buckets,
+                bucket_size: repr.bucket_size,
+                fingerprint_bits: repr.fingerprint_bits,
+                rng,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn insert_remove_contains() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CuckooFilter::<u32, _>::new(1000, 16, 4, builder).unwrap();
+
+        (0..1000u32).for_each(|i| filter.insert(&i).unwrap());
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000u32).all(|i| filter.contains(&i)));
+
+        assert!((0..500u32).all(|i| filter.remove(&i)));
+        assert!((500..1000u32).all(|i| filter.contains(&i)));
+        assert_eq!(filter.len(), 500);
+
+        let false_positives = (1000..101_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 100);
+    }
+
+    #[test]
+    fn full() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = CuckooFilter::<u32, _>::new(64, 12, 2, builder).unwrap();
+
+        let inserted = (0..1000u32)
+            .take_while(|i| filter.insert(i).is_ok())
+            .collect::<Vec<_>>();
+
+        assert!(inserted.len() < 1000);
+        assert_eq!(filter.len(), inserted.len());
+        assert!(inserted.iter().all(|i| filter.contains(i)));
+    }
+
+    #[test]
+    fn invalid_fingerprint_bits() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = CuckooFilter::<u32, _>::new(1000, 33, 4, builder);
+        assert_eq!(res.err(), Some(Error::InvalidFingerprintBits(33)));
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::Display;
+
+/// The errors returned by the filters exposed by the crate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A size parameter (the number of bits, hashes or items) was zero.
+    ZeroSize(&'static str),
+    /// A false-positive probability outside of the open interval (0, 1).
+    InvalidProbability(f64),
+    /// Not enough parameters are known to compute the unknown ones.
+    UnderdeterminedParams,
+    /// The known parameters cannot reach the target false-positive probability.
+    UnsatisfiableParams,
+    /// The maximum value of a counter is zero or greater than the one supported by the counters.
+    InvalidCounterMax(u16),
+    /// The number of bits of a fingerprint is zero or greater than the one supported by the filter.
+    InvalidFingerprintBits(u32),
+    /// The band width of a ribbon filter is zero or greater than 64.
+    InvalidBandWidth(u32),
+    /// A size parameter (the number of bits or hashes) is greater than the one supported by a foreign format.
+    TooLarge(&'static str),
+    /// There is no room left in the filter for a new item.
+    Full,
+    /// The two filters are built with hasher builders which generate different hash values.
+    IncompatibleHashers,
+    /// The two filters have a different number of bits or hash values for each item.
+    IncompatibleSizes,
+    /// The two sketches use different update rules, standard or conservative.
+    IncompatibleUpdates,
+    /// A static filter could not be built from the keys, even after retrying with other seeds.
+    ConstructionFailed,
+    /// An invertible table holds more items than it can list.
+    DecodeFailed,
+    /// The bytes are not a valid serialized filter; the argument names the invalid part.
+    InvalidFormat(&'static str),
+    /// The checksum of a serialized filter does not match its bytes.
+    ChecksumMismatch,
+    /// Reading or writing a serialized filter failed.
+    Io(std::io::ErrorKind),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ZeroSize(name) => write!(f, "the {name} must be greater than zero"),
+            Error::InvalidProbability(p) => {
+                write!(f, "the probability {p} is not in the (0, 1) interval")
+            }
+            Error::UnderdeterminedParams => write!(f, "not enough known parameters"),
+            Error::UnsatisfiableParams => {
+                write!(
+                    f,
+                    "the parameters cannot reach the false-positive probability"
+                )
+            }
+            Error::InvalidCounterMax(max) => {
+                write!(f, "the counter maximum {max} is not supported")
+            }
+            Error::InvalidFingerprintBits(bits) => {
+                write!(f, "fingerprints of {bits} bits are not supported")
+            }
+            Error::InvalidBandWidth(width) => {
+                write!(f, "bands of {width} coefficients are not supported")
+            }
+            Error::TooLarge(name) => write!(f, "the {name} is greater than the format supports"),
+            Error::Full => write!(f, "the filter is full"),
+            Error::IncompatibleHashers => write!(f, "the filters use different hasher builders"),
+            Error::IncompatibleSizes => write!(f, "the filters have different sizes"),
+            Error::IncompatibleUpdates => write!(f, "the sketches use different update rules"),
+            Error::ConstructionFailed => write!(f, "the filter could not be built from the keys"),
+            Error::DecodeFailed => write!(f, "the table holds too many items to be decoded"),
+            Error::InvalidFormat(part) => write!(f, "the serialized filter has an invalid {part}"),
+            Error::ChecksumMismatch => {
+                write!(f, "the checksum of the serialized filter does not match")
+            }
+            Error::Io(kind) => write!(
+                f,
+                "the serialized filter could not be read or written: {kind}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.kind())
+    }
+}
@@ -0,0 +1,439 @@
+use crate::{murmur3::murmur3_x64_128, params, Error};
+use bitvec::vec::BitVec;
+use std::{
+    f64::consts::LN_2,
+    io::{Read, Write},
+};
+
+/// The strategies of Guava which map the hash value of an item to the bits of the filter. Their ordinals
+/// are stored in the serialized filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuavaStrategy {
+    /// `MURMUR128_MITZ_32`, the first strategy, which combines the two halves of the lower 64 bits of the hash
+    /// value. It is kept to read the filters written by old versions of Guava.
+    Murmur128Mitz32,
+    /// `MURMUR128_MITZ_64`, the default strategy, which combines the two 64 bits halves of the hash value.
+    Murmur128Mitz64,
+}
+
+impl GuavaStrategy {
+    fn ordinal(self) -> u8 {
+        match self {
+            GuavaStrategy::Murmur128Mitz32 => 0,
+            GuavaStrategy::Murmur128Mitz64 => 1,
+        }
+    }
+
+    fn from_ordinal(ordinal: u8) -> Result<Self, Error> {
+        match ordinal {
+            0 => Ok(GuavaStrategy::Murmur128Mitz32),
+            1 => Ok(GuavaStrategy::Murmur128Mitz64),
+            _ => Err(Error::InvalidFormat("strategy")),
+        }
+    }
+}
+
+/// A value hashed like a Guava `Funnel` feeds it to `Hashing.murmur3_128()`, which returns the two
+/// 64 bits halves of the hash value. The byte arrays are hashed like `Funnels.byteArrayFunnel()`, the strings
+/// like `Funnels.stringFunnel(UTF_8)`, and the [`i32`] and [`i64`] like `Funnels.integerFunnel()` and
+/// `Funnels.longFunnel()`. The strings hashed like `Funnels.unencodedCharsFunnel()` are wrapped in [`UnencodedChars`].
+pub trait GuavaFunnel {
+    /// Returns the two halves of the hash value of the value.
+    fn guava_hash(&self) -> (u64, u64);
+}
+
+impl GuavaFunnel for [u8] {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(self, 0)
+    }
+}
+
+impl<const N: usize> GuavaFunnel for [u8; N] {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(self, 0)
+    }
+}
+
+impl GuavaFunnel for Vec<u8> {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(self, 0)
+    }
+}
+
+impl GuavaFunnel for str {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(self.as_bytes(), 0)
+    }
+}
+
+impl GuavaFunnel for String {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(self.as_bytes(), 0)
+    }
+}
+
+impl GuavaFunnel for i32 {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(&self.to_le_bytes(), 0)
+    }
+}
+
+impl GuavaFunnel for i64 {
+    fn guava_hash(&self) -> (u64, u64) {
+        murmur3_x64_128(&self.to_le_bytes(), 0)
+    }
+}
+
+impl<T: GuavaFunnel + ?Sized> GuavaFunnel for &T {
+    fn guava_hash(&self) -> (u64, u64) {
+        (**self).guava_hash()
+    }
+}
+
+/// A string hashed like `Funnels.unencodedCharsFunnel()`, as the little-endian bytes of its UTF-16 code units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnencodedChars<'a>(pub &'a str);
+
+impl GuavaFunnel for UnencodedChars<'_> {
+    fn guava_hash(&self) -> (u64, u64) {
+        let bytes: Vec<u8> = self.0.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        murmur3_x64_128(&bytes, 0)
+    }
+}
+
+/// Implements the bloom filter of [Guava](https://github.com/google/guava), so the filters written by
+/// `BloomFilter.writeTo` on the Java side can be read and queried, and the filters built here can be read by
+/// `BloomFilter.readFrom`. The items are [`GuavaFunnel`]s hashed with MurmurHash3, and a [`GuavaStrategy`]
+/// maps the hash value of an item to the bits, so [`GuavaBloomFilter::contains`] answers like `mightContain`.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::GuavaBloomFilter;
+///
+/// let mut filter = GuavaBloomFilter::with_capacity_and_fpr(1000, 0.01).unwrap();
+/// filter.insert("Hello world!");
+///
+/// let bytes = filter.to_bytes();
+/// let filter = GuavaBloomFilter::from_bytes(&bytes).unwrap();
+/// assert!(filter.contains("Hello world!"));
+///```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuavaBloomFilter {
+    bits: BitVec<u64>,
+    hashes: usize,
+    strategy: GuavaStrategy,
+}
+
+impl GuavaBloomFilter {
+    /// Creates a new [`GuavaBloomFilter`] instance with a given number of bits, rounded up to a multiple of 64
+    /// like Guava does, hash values for each item and strategy. Guava stores the number of hash values in a byte
+    /// and the number of 64 bits words in an `int`.
+    pub fn new(bits: usize, hashes: usize, strategy: GuavaStrategy) -> Result<Self, Error> {
+        if bits == 0 {
+            return Err(Error::ZeroSize("number of bits"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        if hashes > u8::MAX as usize {
+            return Err(Error::TooLarge("number of hashes"));
+        }
+
+        let words = bits.div_ceil(64);
+        if words > i32::MAX as usize {
+            return Err(Error::TooLarge("number of bits"));
+        }
+
+        Ok(Self {
+            bits: BitVec::repeat(false, words * 64),
+            hashes,
+            strategy,
+        })
+    }
+
+    /// Creates a new [`GuavaBloomFilter`] instance sized like `BloomFilter.create` sizes a filter for `items`
+    /// items and a false-positive probability `fpr`, with the [`GuavaStrategy::Murmur128Mitz64`] strategy.
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64) -> Result<Self, Error> {
+        if items == 0 {
+            return Err(Error::ZeroSize("number of items"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        // Guava truncates the optimal number of bits, while [`params::optimal_bits`] rounds it up.
+        let bits = (-(items as f64) * fpr.ln() / (LN_2 * LN_2)) as usize;
+        let hashes = params::optimal_hashes(bits, items);
+
+        Self::new(bits, hashes, GuavaStrategy::Murmur128Mitz64)
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn bit_len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Returns the number of hash values computed for each item.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the strategy which maps the hash values to the bits.
+    pub fn strategy(&self) -> GuavaStrategy {
+        self.strategy
+    }
+
+    fn bit_index(&self, (h1, h2): (u64, u64), i: usize) -> usize {
+        let len = self.bits.len() as u64;
+
+        match self.strategy {
+            GuavaStrategy::Murmur128Mitz32 => {
+                let (a, b) = (h1 as i32, (h1 >> 32) as i32);
+                let combined = a.wrapping_add((i as i32 + 1).wrapping_mul(b));
+                let combined = if combined < 0 { !combined } else { combined };
+                (combined as u64 % len) as usize
+            }
+            GuavaStrategy::Murmur128Mitz64 => {
+                let combined = h1.wrapping_add((i as u64).wrapping_mul(h2));
+                ((combined & i64::MAX as u64) % len) as usize
+            }
+        }
+    }
+
+    /// Inserts in the filter a new item.
+    pub fn insert<V: GuavaFunnel + ?Sized>(&mut self, item: &V) {
+        let hash = item.guava_hash();
+        (0..self.hashes).for_each(|i| {
+            let index = self.bit_index(hash, i);
+            self.bits.set(index, true);
+        });
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<V: GuavaFunnel + ?Sized>(&self, item: &V) -> bool {
+        let hash = item.guava_hash();
+        (0..self.hashes).all(|i| self.bits[self.bit_index(hash, i)])
+    }
+
+    /// Serializes the filter like `BloomFilter.writeTo`: the ordinal of the strategy and the number of hash values
+    /// as bytes, the number of 64 bits words as an `int`, and the words, all in big-endian order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let words = self.bits.as_raw_slice();
+        let mut bytes = Vec::with_capacity(6 + words.len() * 8);
+
+        bytes.push(self.strategy.ordinal());
+        bytes.push(self.hashes as u8);
+        bytes.extend_from_slice(&(words.len() as i32).to_be_bytes());
+        words
+            .iter()
+            .for_each(|word| bytes.extend_from_slice(&word.to_be_bytes()));
+
+        bytes
+    }
+
+    /// Deserializes a filter serialized by [`GuavaBloomFilter::to_bytes`] or by `BloomFilter.writeTo`.
+    /// It fails when the bytes are not a valid serialized filter or use an unknown strategy.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = bytes;
+        let filter = Self::read_from(&mut reader)?;
+
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat("trailing bytes"));
+        }
+
+        Ok(filter)
+    }
+
+    /// Writes the serialized filter, see [`GuavaBloomFilter::to_bytes`].
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a filter written by [`GuavaBloomFilter::write_to`], see [`GuavaBloomFilter::from_bytes`].
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header)?;
+
+        let strategy = GuavaStrategy::from_ordinal(header[0])?;
+        let hashes = header[1] as usize;
+        if hashes == 0 {
+            return Err(Error::InvalidFormat("number of hashes"));
+        }
+
+        let words = i32::from_be_bytes(header[2..].try_into().unwrap());
+        if words <= 0 {
+            return Err(Error::InvalidFormat("bit array length"));
+        }
+
+        // The words are read as they arrive, so a corrupted length does not allocate the whole array up front.
+        let len = words as u64 * 8;
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof));
+        }
+
+        let words = bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_be_bytes(word.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            bits: BitVec::from_vec(words),
+            hashes,
+            strategy,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`GuavaBloomFilter`], with the ordinal of its strategy.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        strategy: u8,
+        hashes: usize,
+        bit_len: usize,
+        bits: Bytes,
+    }
+
+    impl Serialize for GuavaBloomFilter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                strategy: self.strategy.ordinal(),
+                hashes: self.hashes,
+                bit_len: self.bits.len(),
+                bits: Bytes::from_bits(&self.bits),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for GuavaBloomFilter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr).map_err(de_error)
+        }
+    }
+
+    impl GuavaBloomFilter {
+        fn from_repr(repr: Repr) -> Result<Self, Error> {
+            let strategy = GuavaStrategy::from_ordinal(repr.strategy)?;
+            let mut filter = Self::new(repr.bit_len, repr.hashes, strategy)?;
+            if filter.bits.len() != repr.bit_len {
+                return Err(Error::InvalidFormat("bit array length"));
+            }
+
+            filter.bits = repr.bits.into_bits(repr.bit_len)?;
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks a filter built like the known false positives tests of Guava's `BloomFilterTest`, which insert
+    /// the even numbers under two millions and list the odd numbers under 900 found in the filter.
+    fn check_known_false_positives<F>(strategy: GuavaStrategy, funnel: F, false_positives: &[usize])
+    where
+        F: Fn(&mut GuavaBloomFilter, &str, bool) -> bool,
+    {
+        let filter = GuavaBloomFilter::with_capacity_and_fpr(1_000_000, 0.03).unwrap();
+        let mut filter =
+            GuavaBloomFilter::new(filter.bit_len(), filter.hashes(), strategy).unwrap();
+
+        (0..2_000_000)
+            .step_by(2)
+            .for_each(|i| _ = funnel(&mut filter, &i.to_string(), true));
+
+        let found: Vec<usize> = (1..900)
+            .step_by(2)
+            .filter(|i| funnel(&mut filter, &i.to_string(), false))
+            .collect();
+        assert_eq!(found, false_positives);
+    }
+
+    fn unencoded_chars(filter: &mut GuavaBloomFilter, item: &str, insert: bool) -> bool {
+        if insert {
+            filter.insert(&UnencodedChars(item));
+        }
+
+        filter.contains(&UnencodedChars(item))
+    }
+
+    fn utf8(filter: &mut GuavaBloomFilter, item: &str, insert: bool) -> bool {
+        if insert {
+            filter.insert(item);
+        }
+
+        filter.contains(item)
+    }
+
+    #[test]
+    fn sizes() {
+        let filter = GuavaBloomFilter::with_capacity_and_fpr(1_000_000, 0.03).unwrap();
+        assert_eq!(filter.bit_len(), 7_298_496);
+        assert_eq!(filter.hashes(), 5);
+
+        let res = GuavaBloomFilter::new(64, 256, GuavaStrategy::Murmur128Mitz64);
+        assert_eq!(res.err(), Some(Error::TooLarge("number of hashes")));
+    }
+
+    #[test]
+    fn known_false_positives_mitz_32() {
+        check_known_false_positives(
+            GuavaStrategy::Murmur128Mitz32,
+            unencoded_chars,
+            &[
+                49, 51, 59, 163, 199, 321, 325, 363, 367, 469, 545, 561, 727, 769, 773, 781,
+            ],
+        );
+    }
+
+    #[test]
+    fn known_false_positives_utf8() {
+        check_known_false_positives(
+            GuavaStrategy::Murmur128Mitz64,
+            utf8,
+            &[89, 129, 471, 723, 751, 835, 871],
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut filter = GuavaBloomFilter::new(100, 3, GuavaStrategy::Murmur128Mitz64).unwrap();
+        filter.insert(&[1u8, 2, 3]);
+        filter.insert(&42i64);
+
+        let bytes = filter.to_bytes();
+        assert_eq!(bytes[..6], [1, 3, 0, 0, 0, 2]);
+        assert_eq!(bytes.len(), 6 + 2 * 8);
+
+        let other = GuavaBloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(other, filter);
+        assert!(other.contains(&[1u8, 2, 3]));
+        assert!(other.contains(&42i64));
+
+        let res = GuavaBloomFilter::from_bytes(&bytes[..bytes.len() - 1]);
+        assert_eq!(
+            res.err(),
+            Some(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+
+        let mut other_strategy = bytes.clone();
+        other_strategy[0] = 2;
+        let res = GuavaBloomFilter::from_bytes(&other_strategy);
+        assert_eq!(res.err(), Some(Error::InvalidFormat("strategy")));
+    }
+}
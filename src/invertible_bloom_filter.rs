@@ -0,0 +1,412 @@
+use crate::{builder_fingerprint, xor_filter::mix, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use std::{
+    hash::{BuildHasher, Hash},
+    ops::BitXor,
+};
+
+/// The constant mixed with the hash value of a key to get the checksum stored in the cells.
+const CHECKSUM_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// The items found by decoding an [`InvertibleBloomFilter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference<T> {
+    /// The items with a positive count. After `a.subtract(&b)`, these are the items only in `a`.
+    pub only_in_self: Vec<T>,
+    /// The items with a negative count. After `a.subtract(&b)`, these are the items only in `b`.
+    pub only_in_other: Vec<T>,
+}
+
+/// A cell of an invertible table, holding the number of keys mapped to it, the xor of the keys
+/// and the xor of their checksums.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Cell<T> {
+    count: i64,
+    key_sum: T,
+    hash_sum: u64,
+}
+
+/// The cells of an invertible table, split into one slice for each hash value of a key.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Table<T> {
+    cells: Vec<Cell<T>>,
+    slice_len: usize,
+    hashes: usize,
+}
+
+impl<T> Table<T>
+where
+    T: Copy + Default + Eq + Hash + BitXor<Output = T>,
+{
+    pub(crate) fn new(slice_len: usize, hashes: usize) -> Self {
+        Self {
+            cells: vec![Cell::default(); slice_len * hashes],
+            slice_len,
+            hashes,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub(crate) fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    pub(crate) fn has_same_shape(&self, other: &Self) -> bool {
+        self.slice_len == other.slice_len && self.hashes == other.hashes
+    }
+
+    /// Adds a key to its cells with a count of `sign`, which is 1 to insert it and -1 to remove it.
+    pub(crate) fn update<B>(&mut self, key: &T, sign: i64, builder: &B)
+    where
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        let checksum = checksum(key, builder);
+
+        self.indexes(key, builder).into_iter().for_each(|index| {
+            let cell = &mut self.cells[index];
+            cell.count += sign;
+            cell.key_sum = cell.key_sum ^ *key;
+            cell.hash_sum ^= checksum;
+        });
+    }
+
+    /// Computes the index of the cell of a key in each slice. The hash values are mixed before being mapped
+    /// to a slice, otherwise two keys would share all their cells with a probability of `1 / s^2`.
+    fn indexes<B>(&self, key: &T, builder: &B) -> Vec<usize>
+    where
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        let slice_len = self.slice_len;
+
+        builder
+            .hashes_one(key)
+            .take(self.hashes)
+            .enumerate()
+            .map(|(slice, hash)| {
+                let hash = mix(hash.into(), slice as u64);
+                slice * slice_len + (hash % slice_len as u64) as usize
+            })
+            .collect()
+    }
+
+    /// Subtracts the cells of a table with the same shape.
+    pub(crate) fn subtract(&mut self, other: &Self) {
+        self.cells.iter_mut().zip(&other.cells).for_each(|(x, y)| {
+            x.count -= y.count;
+            x.key_sum = x.key_sum ^ y.key_sum;
+            x.hash_sum ^= y.hash_sum;
+        });
+    }
+
+    /// Peels the keys from the cells which hold a single key, until all the cells are empty.
+    /// It returns [`Error::DecodeFailed`] when there are no such cells left before all the cells are empty.
+    pub(crate) fn decode<B>(&self, builder: &B) -> Result<Difference<T>, Error>
+    where
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        let mut table = self.clone();
+        let mut difference = Difference {
+            only_in_self: Vec::new(),
+            only_in_other: Vec::new(),
+        };
+
+        let is_pure = |cell: &Cell<T>| {
+            (cell.count == 1 || cell.count == -1)
+                && cell.hash_sum == checksum(&cell.key_sum, builder)
+        };
+
+        let mut queue = (0..table.cells.len())
+            .filter(|index| is_pure(&table.cells[*index]))
+            .collect::<Vec<_>>();
+
+        while let Some(index) = queue.pop() {
+            let cell = table.cells[index];
+            if !is_pure(&cell) {
+                continue;
+            }
+
+            if cell.count > 0 {
+                difference.only_in_self.push(cell.key_sum);
+            } else {
+                difference.only_in_other.push(cell.key_sum);
+            }
+
+            table.update(&cell.key_sum, -cell.count, builder);
+
+            table
+                .indexes(&cell.key_sum, builder)
+                .into_iter()
+                .filter(|index| is_pure(&table.cells[*index]))
+                .for_each(|index| queue.push(index));
+        }
+
+        if table.cells.iter().all(|cell| *cell == Cell::default()) {
+            Ok(difference)
+        } else {
+            Err(Error::DecodeFailed)
+        }
+    }
+}
+
+fn checksum<T, B>(key: &T, builder: &B) -> u64
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    mix(builder.hash_one(key), CHECKSUM_SEED)
+}
+
+/// Implements the [invertible bloom lookup table](https://arxiv.org/abs/1101.2245) of Goodrich and Mitzenmacher,
+/// used to reconcile two sets by exchanging tables which are proportional to the size of their difference instead
+/// of the size of the sets. Each cell holds the number of keys mapped to it, the xor of these keys and the xor of
+/// their checksums, so a cell holding a single key reveals it.
+///
+/// Each replica inserts its keys in a table with the same size and hasher builder. Subtracting the tables cancels
+/// the common keys, and decoding the result peels the keys only in one of the sets, as long as the difference does
+/// not exceed the capacity of the tables.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::InvertibleBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut a = InvertibleBloomFilter::<u64, _>::with_capacity(10, builder()).unwrap();
+/// let mut b = InvertibleBloomFilter::<u64, _>::with_capacity(10, builder()).unwrap();
+///
+/// (0..1000u64).for_each(|i| a.insert(&i));
+/// (2..1001u64).for_each(|i| b.insert(&i));
+///
+/// a.subtract(&b).unwrap();
+/// let mut difference = a.decode().unwrap();
+/// difference.only_in_self.sort();
+///
+/// assert_eq!(difference.only_in_self, vec![0, 1]);
+/// assert_eq!(difference.only_in_other, vec![1000]);
+///```
+pub struct InvertibleBloomFilter<T, B> {
+    builder: B,
+    table: Table<T>,
+}
+
+impl<T, B> InvertibleBloomFilter<T, B>
+where
+    T: Copy + Default + Eq + Hash + BitXor<Output = T>,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`InvertibleBloomFilter`] instance with at least `cells` cells, split in `hashes` slices.
+    pub fn new(cells: usize, hashes: usize, builder: B) -> Result<Self, Error> {
+        if cells == 0 {
+            return Err(Error::ZeroSize("number of cells"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        Ok(Self {
+            builder,
+            table: Table::new(cells.div_ceil(hashes), hashes),
+        })
+    }
+
+    /// Creates a new [`InvertibleBloomFilter`] instance which decodes, with high probability, a difference
+    /// of up to `capacity` items.
+    pub fn with_capacity(capacity: usize, builder: B) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(Error::ZeroSize("capacity"));
+        }
+
+        // Peeling with four hash values succeeds once there are more than 1.3 cells for each item,
+        // and the constant term covers the small differences.
+        Self::new(capacity * 3 / 2 + 30, 4, builder)
+    }
+
+    /// Returns the number of cells in the table.
+    pub fn cells(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns the number of hash values computed for each item.
+    pub fn hashes(&self) -> usize {
+        self.table.hashes()
+    }
+
+    /// Inserts in the table a new item.
+    pub fn insert(&mut self, item: &T) {
+        self.table.update(item, 1, &self.builder);
+    }
+
+    /// Removes an item from the table. Removing an item which was not inserted leaves it with a negative
+    /// count, so it is decoded as an item of [`Difference::only_in_other`].
+    pub fn remove(&mut self, item: &T) {
+        self.table.update(item, -1, &self.builder);
+    }
+
+    /// Subtracts from the table the items of another table. It fails when the tables have different sizes
+    /// or when they are built with different hasher builders.
+    pub fn subtract(&mut self, other: &Self) -> Result<(), Error> {
+        if !self.table.has_same_shape(&other.table) {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        self.table.subtract(&other.table);
+        Ok(())
+    }
+
+    /// Lists the items of the table. It returns [`Error::DecodeFailed`] when the table holds too many items
+    /// to be decoded, which happens when the difference of two tables exceeds their capacity.
+    pub fn decode(&self) -> Result<Difference<T>, Error> {
+        self.table.decode(&self.builder)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{check_hasher, de_error};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of an [`InvertibleBloomFilter`], which borrows the table when serializing
+    /// and owns it when deserializing.
+    #[derive(Serialize, Deserialize)]
+    struct Repr<S> {
+        hasher: u64,
+        table: S,
+    }
+
+    impl<T> Table<T> {
+        /// Checks that a deserialized table has one cell for each slot of each slice.
+        pub(crate) fn check_shape(&self) -> Result<(), Error> {
+            if self.slice_len == 0 || self.hashes == 0 {
+                return Err(Error::InvalidFormat("empty table"));
+            }
+
+            if self.cells.len() != self.slice_len.saturating_mul(self.hashes) {
+                return Err(Error::InvalidFormat("number of cells"));
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<T, B> Serialize for InvertibleBloomFilter<T, B>
+    where
+        T: Copy + Serialize,
+        B: BuildHasher,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                table: &self.table,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for InvertibleBloomFilter<T, B>
+    where
+        T: Copy + Default + Eq + Hash + BitXor<Output = T> + Deserialize<'de>,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> InvertibleBloomFilter<T, B>
+    where
+        T: Copy + Default + Eq + Hash + BitXor<Output = T>,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a table with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the table was built with a different hasher builder.
+        pub fn deserialize_with<'de, D>(deserializer: D, builder: B) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de>,
+        {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr<Table<T>>, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+            repr.table.check_shape()?;
+
+            Ok(Self {
+                builder,
+                table: repr.table,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn insert_remove_decode() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut table = InvertibleBloomFilter::<u32, _>::with_capacity(100, builder).unwrap();
+
+        (0..100u32).for_each(|i| table.insert(&i));
+        (50..100u32).for_each(|i| table.remove(&i));
+        table.remove(&1000);
+
+        let mut difference = table.decode().unwrap();
+        difference.only_in_self.sort();
+
+        assert_eq!(difference.only_in_self, (0..50u32).collect::<Vec<_>>());
+        assert_eq!(difference.only_in_other, vec![1000]);
+    }
+
+    #[test]
+    fn subtract() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut a = InvertibleBloomFilter::<u64, _>::with_capacity(1000, builder()).unwrap();
+        let mut b = InvertibleBloomFilter::<u64, _>::with_capacity(1000, builder()).unwrap();
+
+        (0..100_000u64).for_each(|i| a.insert(&i));
+        (500..100_500u64).for_each(|i| b.insert(&i));
+
+        a.subtract(&b).unwrap();
+        let mut difference = a.decode().unwrap();
+        difference.only_in_self.sort();
+        difference.only_in_other.sort();
+
+        assert_eq!(difference.only_in_self, (0..500u64).collect::<Vec<_>>());
+        assert_eq!(
+            difference.only_in_other,
+            (100_000..100_500u64).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn decode_failed() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut table = InvertibleBloomFilter::<u64, _>::with_capacity(10, builder).unwrap();
+
+        (0..1000u64).for_each(|i| table.insert(&i));
+        assert_eq!(table.decode(), Err(Error::DecodeFailed));
+    }
+}
@@ -28,27 +28,176 @@
 //! let contains = filter.contains(item);
 //! assert!(contains)
 //!```
+//!
+//! A [`BloomFilter`] can be persisted or shipped between services with [`BloomFilter::to_bytes`] and
+//! [`BloomFilter::from_bytes`], whose format does not depend on the platform. With the `serde` feature,
+//! all the filters implement `Serialize`, and `Deserialize` when the hasher builder implements [`Default`].
+//! The other builders are passed to the `deserialize_with` function of each filter. The serialized filters
+//! store their bits as bytes and record a fingerprint of the hasher builder and their sizes, so they cannot be
+//! deserialized into a filter with a different hasher builder or, for [`BloomFilter`], different `K` or `H`.
+//!
+//! An [`AtomicBloomFilter`] is shared between threads without a lock, and its bits are copied into a
+//! [`BloomFilter`] to be serialized.
+//!
+//! When the size of the filter is known only at runtime, use [`VecBloomFilter`] which can be
+//! sized from the expected number of items and a target false-positive rate. The [`params`] module
+//! computes the `K` and `H` arguments of [`BloomFilter`] for a given target.
+//!
+//! Items can be removed from a [`CountingBloomFilter`], which stores small counters instead of bits.
+//! When the number of items is not known up front, a [`ScalableBloomFilter`] grows as items arrive.
+//! A [`BlockedBloomFilter`] keeps the bits of each item in a single cache line, and a
+//! [`PartitionedBloomFilter`] sets each bit of an item in its own slice of the bit array.
+//! A [`StableBloomFilter`] evicts old items to deduplicate unbounded streams. The [`SplitBlockBloomFilter`]
+//! reads and writes the bloom filters stored in Parquet files, with the hashing fixed by the format.
+//! The [`GuavaBloomFilter`] reads and writes the filters serialized by Guava's `BloomFilter.writeTo`, and
+//! answers like the Java filter for the items hashed by the same funnel.
+//! The [`RedisBloomFilter`] is the scalable filter of RedisBloom, rebuilt from the chunks of `BF.SCANDUMP`
+//! and split into the chunks of `BF.LOADCHUNK`.
+//! The [`RocksDbBloomFilter`] and the [`CassandraBloomFilter`] read the filters stored in the tables of RocksDB
+//! and Cassandra, so the keys can be tested against a table without the database.
+//!
+//! Besides the bloom filters, the crate implements other approximate membership filters:
+//! - [`CuckooFilter`], which supports removing items and uses less space for low false-positive rates.
+//! - [`QuotientFilter`], which stores the fingerprints of the items, so filters can be merged and resized
+//!   without hashing the items again.
+//! - [`XorFilter`] and [`BinaryFuseFilter`], static filters built once from a complete set of keys,
+//!   which use less space than a bloom filter but cannot be updated.
+//! - [`RibbonFilter`], a static filter which solves a banded linear system to get close to the minimal space.
+//!
+//! The [`CountMinSketch`] shares the same hashing to estimate how many times each item was added,
+//! and the [`InvertibleBloomFilter`] finds the difference of two sets held by different replicas,
+//! after a [`StrataEstimator`] estimates its size.
+
+mod atomic_bloom_filter;
+mod binary_fuse_filter;
+mod blocked_bloom_filter;
+mod cassandra_bloom_filter;
+mod count_min_sketch;
+mod counters;
+mod counting_bloom_filter;
+mod cuckoo_filter;
+mod error;
+mod guava_bloom_filter;
+mod invertible_bloom_filter;
+mod murmur2;
+mod murmur3;
+pub mod params;
+mod partitioned_bloom_filter;
+mod quotient_filter;
+mod redis_bloom_filter;
+mod ribbon_filter;
+mod rocksdb_bloom_filter;
+mod scalable_bloom_filter;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod serialization;
+mod split_block_bloom_filter;
+mod stable_bloom_filter;
+mod stats;
+mod strata_estimator;
+mod thrift;
+mod vec_bloom_filter;
+mod xor_filter;
+mod xxhash;
+mod xxph3;
+
+pub use atomic_bloom_filter::*;
+pub use binary_fuse_filter::*;
+pub use blocked_bloom_filter::*;
+pub use cassandra_bloom_filter::*;
+pub use count_min_sketch::*;
+pub use counters::*;
+pub use counting_bloom_filter::*;
+pub use cuckoo_filter::*;
+pub use error::*;
+pub use guava_bloom_filter::*;
+pub use invertible_bloom_filter::*;
+pub use partitioned_bloom_filter::*;
+pub use quotient_filter::*;
+pub use redis_bloom_filter::*;
+pub use ribbon_filter::*;
+pub use rocksdb_bloom_filter::*;
+pub use scalable_bloom_filter::*;
+pub use split_block_bloom_filter::*;
+pub use stable_bloom_filter::*;
+pub use stats::*;
+pub use strata_estimator::*;
+pub use vec_bloom_filter::*;
+pub use xor_filter::*;
 
 use aabel_multihash_rs::{BuildHasherExt, Hash64, HasherExt};
-use bitvec::array::BitArray;
+use bitvec::{array::BitArray, mem::BitRegister};
 use std::{
     borrow::Borrow,
     hash::{BuildHasher, Hash},
     marker::PhantomData,
+    ops::{BitAnd, BitOr},
 };
 
+/// Maps a hash value to the index of a bit in an array with `len` bits. The whole 64 bits hash value
+/// is reduced, so the index does not depend on the width of `usize`.
+pub(crate) fn bit_index(hash: Hash64, len: usize) -> usize {
+    let hash: u64 = hash.into();
+    (hash % len as u64) as usize
+}
+
+/// The item hashed by [`builder_fingerprint`].
+const FINGERPRINT_PROBE: &str = "aabel-bloom-rs";
+
+/// Computes a fingerprint of a hasher builder from the first two hash values of a probe. Two builders with
+/// the same fingerprint are assumed to generate the same hash values, so the filters built with them can be
+/// combined. [`BuildHasher::hash_one`] is not enough, since a [`aabel_multihash_rs::BuildPairHasher`] returns
+/// the same value when its two keys are swapped, while its sequences of hash values differ.
+pub(crate) fn builder_fingerprint<B>(builder: &B) -> u64
+where
+    B: BuildHasher,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    let mut hashes = builder.hashes_one(FINGERPRINT_PROBE).map(u64::from);
+    let first = hashes.next().expect("the hash sequence is infinite");
+    let second = hashes.next().expect("the hash sequence is infinite");
+
+    xor_filter::mix(first, 0) ^ second
+}
+
+/// Sets in a raw bit array the bits set in another raw bit array.
+pub(crate) fn union_raw<W: BitRegister>(xs: &mut [W], ys: &[W]) {
+    xs.iter_mut().zip(ys).for_each(|(x, y)| *x |= *y);
+}
+
+/// Clears in a raw bit array the bits which are not set in another raw bit array.
+pub(crate) fn intersect_raw<W: BitRegister>(xs: &mut [W], ys: &[W]) {
+    xs.iter_mut().zip(ys).for_each(|(x, y)| *x &= *y);
+}
+
+/// Counts the bits set in the union of two raw bit arrays.
+pub(crate) fn union_count_ones<W: BitRegister>(xs: &[W], ys: &[W]) -> usize {
+    xs.iter()
+        .zip(ys)
+        .map(|(x, y)| (*x | *y).count_ones() as usize)
+        .sum()
+}
+
 /// Implements the [bloom filter](https://en.wikipedia.org/wiki/Bloom_filter).
 /// [`B`] is an instance of [`BuildHasherExt`] trait which helps generating multiple hash values for any given item [`T`].
-/// The [`K`] generic argument represents the number of usize cells in the inner array.
+/// The [`K`] generic argument represents the number of 64 bits words in the inner array, so a filter has `64 * K` bits
+/// and maps an item to the same bits on every platform.
 /// The [`H`] generic argument represents the number of hash values computed for each item.
 ///
+/// # Migration
+///
+/// Before `K` counted 64 bits words, it counted `usize` cells. Nothing changes on 64-bit targets, where the filters
+/// have the same bits and [`BloomFilter::from_usize_cells`] rebuilds a filter from the cells of the old layout.
+/// On 32-bit targets the old filters had `32 * K` bits and truncated the hash values to 32 bits, so their items
+/// have to be inserted again in a filter with `K / 2` words, rounded up.
+///
 /// # Example
 pub struct BloomFilter<T, B, const K: usize = 100, const H: usize = 10>
 where
     T: ?Sized,
 {
     builder: B,
-    bits: BitArray<[usize; K]>,
+    bits: BitArray<[u64; K]>,
     _marker: PhantomData<T>,
 }
 
@@ -56,6 +205,7 @@ impl<T, B, const K: usize, const H: usize> BloomFilter<T, B, K, H>
 where
     T: ?Sized,
     B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
 {
     /// Creates a new [`BloomFilter`] instance based on a given [`BuildHasherExt`] instance and a give number of hash values for each item.
     pub fn new(builder: B) -> Self {
@@ -65,6 +215,129 @@ where
             _marker: PhantomData,
         }
     }
+
+    /// Creates a new [`BloomFilter`] instance from the words of another filter, see [`BloomFilter::as_words`].
+    pub fn from_words(words: [u64; K], builder: B) -> Self {
+        Self {
+            builder,
+            bits: BitArray::new(words),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new [`BloomFilter`] instance from the `usize` cells of a filter with the layout used before `K`
+    /// counted 64 bits words. It is only available on 64-bit targets, where the two layouts have the same bits.
+    #[cfg(target_pointer_width = "64")]
+    pub fn from_usize_cells(cells: [usize; K], builder: B) -> Self {
+        Self::from_words(cells.map(|cell| cell as u64), builder)
+    }
+
+    /// Returns the words of the filter. The i-th bit of the filter is the bit `i % 64` of the word `i / 64`.
+    pub fn as_words(&self) -> &[u64; K] {
+        &self.bits.data
+    }
+
+    /// Returns the number of bits set in the filter.
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    /// Estimates the number of distinct items inserted in the filter from the number of bits set.
+    ///
+    /// # Example
+    ///
+    ///```
+    /// use aabel_bloom_rs::*;
+    /// use aabel_multihash_rs::*;
+    ///
+    /// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+    /// let mut filter = BloomFilter::<u32, _>::new(builder);
+    ///
+    /// (0..100u32).for_each(|i| filter.insert(&i));
+    ///
+    /// let len = filter.estimated_len();
+    /// assert!(len > 90.0 && len < 110.0);
+    ///```
+    pub fn estimated_len(&self) -> f64 {
+        params::estimated_items(self.bits.len(), H, self.count_ones())
+    }
+
+    /// Returns the saturation of the filter and its current false-positive probability.
+    pub fn stats(&self) -> Stats {
+        Stats::new(self.bits.len(), H, self.count_ones())
+    }
+
+    /// Estimates the number of distinct items in the union of the items inserted in two filters.
+    /// It returns an error when the two filters are built with different hasher builders.
+    pub fn estimated_union_len(&self, other: &Self) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+
+        let ones = union_count_ones(self.bits.as_raw_slice(), other.bits.as_raw_slice());
+        Ok(params::estimated_items(self.bits.len(), H, ones))
+    }
+
+    /// Estimates the number of distinct items inserted in both filters.
+    /// It returns an error when the two filters are built with different hasher builders.
+    pub fn estimated_intersection_len(&self, other: &Self) -> Result<f64, Error> {
+        let union = self.estimated_union_len(other)?;
+        Ok((self.estimated_len() + other.estimated_len() - union).max(0.0))
+    }
+
+    /// Adds to the filter the items inserted in another filter.
+    /// It returns an error when the two filters are built with different hasher builders.
+    ///
+    /// # Example
+    ///
+    ///```
+    /// use aabel_bloom_rs::*;
+    /// use aabel_multihash_rs::*;
+    ///
+    /// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+    /// let mut filter1 = BloomFilter::<str, _>::new(builder());
+    /// let mut filter2 = BloomFilter::<str, _>::new(builder());
+    ///
+    /// filter1.insert("Hello");
+    /// filter2.insert("world!");
+    ///
+    /// filter1.union_with(&filter2).unwrap();
+    /// assert!(filter1.contains("Hello"));
+    /// assert!(filter1.contains("world!"));
+    ///```
+    pub fn union_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+
+        union_raw(self.bits.as_raw_mut_slice(), other.bits.as_raw_slice());
+        Ok(())
+    }
+
+    /// Keeps in the filter only the bits which are also set in another filter.
+    /// It returns an error when the two filters are built with different hasher builders.
+    pub fn intersect_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+
+        intersect_raw(self.bits.as_raw_mut_slice(), other.bits.as_raw_slice());
+        Ok(())
+    }
+
+    /// Returns the union of two filters, see [`BloomFilter::union_with`].
+    pub fn union(mut self, other: &Self) -> Result<Self, Error> {
+        self.union_with(other)?;
+        Ok(self)
+    }
+
+    /// Returns the intersection of two filters, see [`BloomFilter::intersect_with`].
+    pub fn intersect(mut self, other: &Self) -> Result<Self, Error> {
+        self.intersect_with(other)?;
+        Ok(self)
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), Error> {
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        Ok(())
+    }
 }
 
 impl<T, B, const K: usize, const H: usize> BloomFilter<T, B, K, H>
@@ -97,8 +370,7 @@ where
         U: Hash + ?Sized,
     {
         let set_bit_for_hash = |hash: Hash64| {
-            let hash: u64 = hash.into();
-            let index = hash as usize % self.bits.len();
+            let index = bit_index(hash, self.bits.len());
             self.bits.set(index, true);
         };
 
@@ -136,8 +408,7 @@ where
         U: Hash + ?Sized,
     {
         let get_bit_for_hash = |hash: Hash64| {
-            let hash: u64 = hash.into();
-            let index = hash as usize % self.bits.len();
+            let index = bit_index(hash, self.bits.len());
             self.bits[index]
         };
 
@@ -145,6 +416,32 @@ where
     }
 }
 
+impl<T, B, const K: usize, const H: usize> BitOr for BloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    type Output = Result<Self, Error>;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(&rhs)
+    }
+}
+
+impl<T, B, const K: usize, const H: usize> BitAnd for BloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    type Output = Result<Self, Error>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersect(&rhs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,4 +463,78 @@ mod tests {
         let res = filter.contains(item);
         assert!(res)
     }
+
+    #[test]
+    fn estimated_union_intersection() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter1 = BloomFilter::<u32, _>::new(builder());
+        let mut filter2 = BloomFilter::<u32, _>::new(builder());
+
+        (0..100u32).for_each(|i| filter1.insert(&i));
+        (50..150u32).for_each(|i| filter2.insert(&i));
+
+        let union = filter1.estimated_union_len(&filter2).unwrap();
+        assert!((union - 150.0).abs() < 15.0);
+
+        let intersection = filter1.estimated_intersection_len(&filter2).unwrap();
+        assert!((intersection - 50.0).abs() < 15.0);
+
+        let other = BloomFilter::<u32, _>::new(BuildPairHasher::new_with_keys((2, 2), (3, 3)));
+        let res = filter1.estimated_union_len(&other);
+        assert_eq!(res, Err(Error::IncompatibleHashers));
+    }
+
+    #[test]
+    fn union_intersect_operators() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let new_filter = |items: &[&str]| {
+            let mut filter = BloomFilter::<str, _>::new(builder());
+            items.iter().for_each(|item| filter.insert(*item));
+            filter
+        };
+
+        let mut union = (new_filter(&["a", "b"]) | new_filter(&["b", "c"])).unwrap();
+        assert!(["a", "b", "c"].iter().all(|item| union.contains(*item)));
+
+        let mut intersection = (new_filter(&["a", "b"]) & new_filter(&["b", "c"])).unwrap();
+        assert!(intersection.contains("b"));
+        assert!(intersection.count_ones() <= union.count_ones());
+
+        let other = BloomFilter::<str, _>::new(BuildPairHasher::new_with_keys((2, 2), (3, 3)));
+        let res = intersection.intersect_with(&other);
+        assert_eq!(res, Err(Error::IncompatibleHashers));
+    }
+
+    #[test]
+    fn swapped_keys() {
+        // The two builders hash to the same value, but generate different sequences of hash values.
+        let mut filter1 =
+            BloomFilter::<str, _>::new(BuildPairHasher::new_with_keys((1, 2), (3, 4)));
+        let mut filter2 =
+            BloomFilter::<str, _>::new(BuildPairHasher::new_with_keys((3, 4), (1, 2)));
+        filter1.insert("Hello world!");
+        filter2.insert("Hello world!");
+
+        assert_ne!(filter1.as_words(), filter2.as_words());
+        assert_eq!(
+            filter1.union_with(&filter2),
+            Err(Error::IncompatibleHashers)
+        );
+    }
+
+    #[test]
+    fn words() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = BloomFilter::<str, _, 3, 4>::new(builder());
+        filter.insert("Hello world!");
+
+        // The bits do not depend on the width of `usize`.
+        assert_eq!(
+            filter.as_words(),
+            &[0x0800_0000_0008, 0x1000_0000_0008_0000, 0]
+        );
+
+        let mut other = BloomFilter::<str, _, 3, 4>::from_words(*filter.as_words(), builder());
+        assert!(other.contains("Hello world!"));
+    }
 }
@@ -0,0 +1,81 @@
+/// The multiplier of the 32 bits [MurmurHash2](https://github.com/aappleby/smhasher/blob/master/src/MurmurHash2.cpp).
+const M32: u32 = 0x5bd1_e995;
+
+/// The multiplier of MurmurHash64A.
+const M64: u64 = 0xc6a4_a793_5bd1_e995;
+
+/// Computes the 32 bits MurmurHash2 of some bytes with a given seed.
+pub(crate) fn murmur2(bytes: &[u8], seed: u32) -> u32 {
+    let mut h = seed ^ bytes.len() as u32;
+
+    let mut blocks = bytes.chunks_exact(4);
+    for block in &mut blocks {
+        let mut k = u32::from_le_bytes(block.try_into().unwrap());
+        k = k.wrapping_mul(M32);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M32);
+
+        h = h.wrapping_mul(M32) ^ k;
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        h ^= tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, byte| k << 8 | *byte as u32);
+        h = h.wrapping_mul(M32);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M32);
+    h ^ (h >> 15)
+}
+
+/// Computes the 64 bits MurmurHash64A of some bytes with a given seed.
+pub(crate) fn murmur64a(bytes: &[u8], seed: u64) -> u64 {
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(M64);
+
+    let mut blocks = bytes.chunks_exact(8);
+    for block in &mut blocks {
+        let mut k = u64::from_le_bytes(block.try_into().unwrap());
+        k = k.wrapping_mul(M64);
+        k ^= k >> 47;
+        k = k.wrapping_mul(M64);
+
+        h ^= k;
+        h = h.wrapping_mul(M64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        h ^= tail
+            .iter()
+            .rev()
+            .fold(0u64, |k, byte| k << 8 | *byte as u64);
+        h = h.wrapping_mul(M64);
+    }
+
+    h ^= h >> 47;
+    h = h.wrapping_mul(M64);
+    h ^ (h >> 47)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur2_vectors() {
+        assert_eq!(murmur2(b"", 0x9747_b28c), 0x106e_08d9);
+        assert_eq!(murmur2(b"foo", 0x9747_b28c), 0x23a2_56d0);
+        assert_eq!(murmur2(b"Hello world!", 0x9747_b28c), 0xa81b_91a8);
+    }
+
+    #[test]
+    fn murmur64a_vectors() {
+        assert_eq!(murmur64a(b"", 0), 0);
+        assert_eq!(murmur64a(b"a", M64), 0x4292_cee2_27b9_150a);
+        assert_eq!(murmur64a(b"Hello world!", M64), 0xe98a_2851_383c_d730);
+    }
+}
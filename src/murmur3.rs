@@ -0,0 +1,119 @@
+const C1: u64 = 0x87c3_7b91_1142_53d5;
+const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+fn mix_k1(k1: u64) -> u64 {
+    k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
+}
+
+fn mix_k2(k2: u64) -> u64 {
+    k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
+}
+
+/// Computes the [MurmurHash3](https://github.com/aappleby/smhasher/blob/master/src/MurmurHash3.cpp) x64 128 bits
+/// hash of some bytes with a given seed, and returns its two 64 bits halves.
+pub(crate) fn murmur3_x64_128(bytes: &[u8], seed: u64) -> (u64, u64) {
+    hash(bytes, seed, |byte| byte as u64)
+}
+
+/// Computes the MurmurHash3 x64 128 bits hash like Cassandra does, which sign-extends the bytes of the tail.
+/// The hash values differ from [`murmur3_x64_128`] only when one of the last `len % 16` bytes is above 0x7f.
+pub(crate) fn cassandra_murmur3_x64_128(bytes: &[u8], seed: u64) -> (u64, u64) {
+    hash(bytes, seed, |byte| byte as i8 as u64)
+}
+
+fn hash(bytes: &[u8], seed: u64, extend: fn(u8) -> u64) -> (u64, u64) {
+    let (mut h1, mut h2) = (seed, seed);
+
+    let mut blocks = bytes.chunks_exact(16);
+    for block in &mut blocks {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+
+        h1 ^= mix_k1(k1);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+
+        h2 ^= mix_k2(k2);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+
+    let tail = blocks.remainder();
+    let load = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(0u64, |k, (i, byte)| k ^ extend(*byte) << (8 * i))
+    };
+
+    if tail.len() > 8 {
+        h2 ^= mix_k2(load(&tail[8..]));
+    }
+
+    if !tail.is_empty() {
+        h1 ^= mix_k1(load(&tail[..tail.len().min(8)]));
+    }
+
+    h1 ^= bytes.len() as u64;
+    h2 ^= bytes.len() as u64;
+
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+
+    (h1, h2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur3_vectors() {
+        assert_eq!(murmur3_x64_128(b"", 0), (0, 0));
+        assert_eq!(
+            murmur3_x64_128(b"The quick brown fox jumps over the lazy dog", 0),
+            (0xe34b_bc7b_bc07_1b6c, 0x7a43_3ca9_c49a_9347)
+        );
+    }
+
+    #[test]
+    fn cassandra_vectors() {
+        assert_eq!(
+            cassandra_murmur3_x64_128(b"abc", 0),
+            murmur3_x64_128(b"abc", 0)
+        );
+        assert_eq!(
+            cassandra_murmur3_x64_128(&[0xff, 0x80, 0x7f], 0),
+            (0x0089_ee9b_941a_ba0d, 0x3c99_158b_3e30_72cf)
+        );
+
+        let bytes = [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 0xfe, 0xfd, 3, 4, 5, 6, 7, 8,
+            0x99,
+        ];
+        assert_eq!(
+            cassandra_murmur3_x64_128(&bytes, 0),
+            (0x1ce9_9028_1092_800a, 0x19d8_6a7c_7a40_5a40)
+        );
+    }
+}
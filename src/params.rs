@@ -0,0 +1,364 @@
+//! Calculators for the parameters of a bloom filter: the number of bits `m`, the number of hash values
+//! computed for each item `k`, the number of expected items `n` and the false-positive probability `p`.
+//!
+//! # Example
+//!
+//!```
+//! use aabel_bloom_rs::params::Params;
+//!
+//! // Find the BloomFilter type for 1000 items with a 1% false-positive rate.
+//! let solution = Params::new().items(1000).fpr(0.01).solve().unwrap();
+//! assert_eq!(solution.bits, 9593);
+//! assert_eq!(solution.hashes, 7);
+//!
+//! let rounded = solution.rounded();
+//! assert_eq!(rounded.bits, 9600);
+//! assert_eq!(rounded.type_params(), format!("BloomFilter<_, _, {}, 7>", rounded.cells()));
+//!```
+
+use crate::Error;
+use std::f64::consts::LN_2;
+
+/// The asymptotic false-positive probability `(1 - e^(-kn/m))^k` of a filter with `bits` bits
+/// and `hashes` hash values for each item, after inserting `items` items.
+pub fn false_positive_rate(bits: usize, hashes: usize, items: usize) -> f64 {
+    let (m, k, n) = (bits as f64, hashes as f64, items as f64);
+    (1.0 - (-k * n / m).exp()).powf(k)
+}
+
+/// The non-asymptotic false-positive probability `(1 - (1 - 1/m)^(kn))^k` of a filter with `bits` bits
+/// and `hashes` hash values for each item, after inserting `items` items. It is more accurate than
+/// [`false_positive_rate`] for small filters.
+pub fn exact_false_positive_rate(bits: usize, hashes: usize, items: usize) -> f64 {
+    let (m, k, n) = (bits as f64, hashes as f64, items as f64);
+    let zero = (k * n * (-1.0 / m).ln_1p()).exp();
+    (1.0 - zero).powf(k)
+}
+
+/// The false-positive probability of a blocked filter with `blocks` blocks of `block_bits` bits, where each item
+/// sets `hashes` bits in a single block, after inserting `items` items. The number of items in a block follows
+/// a Poisson distribution, so the fuller blocks make the probability higher than the one of the classic layout.
+pub fn blocked_false_positive_rate(
+    blocks: usize,
+    block_bits: usize,
+    hashes: usize,
+    items: usize,
+) -> f64 {
+    let lambda = items as f64 / blocks as f64;
+    let upper = (lambda + 10.0 * lambda.sqrt() + 10.0).ceil() as usize;
+
+    let mut ln_pmf = -lambda;
+    (0..=upper)
+        .map(|i| {
+            if i > 0 {
+                ln_pmf += lambda.ln() - (i as f64).ln();
+            }
+
+            ln_pmf.exp() * exact_false_positive_rate(block_bits, hashes, i)
+        })
+        .sum()
+}
+
+/// The number of bits `-8 n / ln(1 - p^(1/8))` of a split block bloom filter, which sets eight bits
+/// in a block of 256 bits for each item, for `items` items and a false-positive probability `fpr`.
+/// It is truncated like the sizing of the Parquet writers, so the filters get the same number of bytes.
+pub fn split_block_bits(items: usize, fpr: f64) -> usize {
+    let n = items as f64;
+    (-8.0 * n / (1.0 - fpr.powf(1.0 / 8.0)).ln()) as usize
+}
+
+/// The false-positive probability at the stable point of a [stable bloom filter](https://doi.org/10.1145/1142473.1142477)
+/// with `cells` counters saturating at `max`, `hashes` hash values for each item and `decrements` counters
+/// decremented on each insertion. It is `(1 - (1 / (1 + 1 / (P (1/k - 1/m))))^max)^k`.
+pub fn stable_false_positive_rate(cells: usize, hashes: usize, max: u16, decrements: usize) -> f64 {
+    let (m, k, p) = (cells as f64, hashes as f64, decrements as f64);
+    let zero = (1.0 / (1.0 + 1.0 / (p * (1.0 / k - 1.0 / m)))).powi(max as i32);
+    (1.0 - zero).powf(k)
+}
+
+/// The number of counters to decrement on each insertion of a stable bloom filter with `cells` counters
+/// saturating at `max` and `hashes` hash values for each item, so the false-positive probability at the
+/// stable point is not greater than `fpr`.
+pub fn stable_decrements(cells: usize, hashes: usize, max: u16, fpr: f64) -> usize {
+    let (m, k) = (cells as f64, hashes as f64);
+    let zero = 1.0 - fpr.powf(1.0 / k);
+    let p = 1.0 / ((zero.powf(-1.0 / max as f64) - 1.0) * (1.0 / k - 1.0 / m));
+    p.ceil().max(1.0) as usize
+}
+
+/// The optimal number of bits `-n ln(p) / ln(2)^2` for `items` items and a false-positive probability `fpr`.
+pub fn optimal_bits(items: usize, fpr: f64) -> usize {
+    let n = items as f64;
+    (-n * fpr.ln() / (LN_2 * LN_2)).ceil() as usize
+}
+
+/// The optimal number of hash values `m / n ln(2)` for a filter with `bits` bits and `items` items.
+pub fn optimal_hashes(bits: usize, items: usize) -> usize {
+    let (m, n) = (bits as f64, items as f64);
+    (m / n * LN_2).round().max(1.0) as usize
+}
+
+/// The optimal number of hash values `-log2(p)` for a false-positive probability `fpr`.
+pub fn optimal_hashes_for_fpr(fpr: f64) -> usize {
+    (-fpr.log2()).round().max(1.0) as usize
+}
+
+/// The maximum number of items which can be inserted in a filter with `bits` bits and `hashes`
+/// hash values for each item while keeping the false-positive probability under `fpr`.
+pub fn max_items(bits: usize, hashes: usize, fpr: f64) -> usize {
+    let (m, k) = (bits as f64, hashes as f64);
+    (-m / k * (-fpr.powf(1.0 / k)).ln_1p()).floor() as usize
+}
+
+/// The minimum number of bits of a filter with `hashes` hash values for each item
+/// which keeps the false-positive probability under `fpr` after inserting `items` items.
+pub fn min_bits(hashes: usize, items: usize, fpr: f64) -> usize {
+    let (k, n) = (hashes as f64, items as f64);
+    (-k * n / (-fpr.powf(1.0 / k)).ln_1p()).ceil() as usize
+}
+
+/// The [Swamidass–Baldi](https://doi.org/10.1021/ci600358f) estimate `-m / k ln(1 - x / m)` of the number
+/// of distinct items inserted in a filter with `bits` bits, `hashes` hash values for each item and `ones` bits set.
+/// The estimate is infinite when all the bits are set.
+pub fn estimated_items(bits: usize, hashes: usize, ones: usize) -> f64 {
+    let (m, k, x) = (bits as f64, hashes as f64, ones as f64);
+    -m / k * (-x / m).ln_1p()
+}
+
+/// The number of 64 bits words needed to store `bits` bits, that is the `K` argument of [`crate::BloomFilter`].
+pub fn cells_for_bits(bits: usize) -> usize {
+    bits.div_ceil(u64::BITS as usize)
+}
+
+/// The known parameters of a bloom filter. The unknown ones are computed by [`Params::solve`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Params {
+    bits: Option<usize>,
+    hashes: Option<usize>,
+    items: Option<usize>,
+    fpr: Option<f64>,
+}
+
+impl Params {
+    /// Creates a new [`Params`] instance with all the parameters unknown.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of bits of the filter.
+    pub fn bits(self, bits: usize) -> Self {
+        Self {
+            bits: Some(bits),
+            ..self
+        }
+    }
+
+    /// Sets the number of hash values computed for each item.
+    pub fn hashes(self, hashes: usize) -> Self {
+        Self {
+            hashes: Some(hashes),
+            ..self
+        }
+    }
+
+    /// Sets the number of expected items.
+    pub fn items(self, items: usize) -> Self {
+        Self {
+            items: Some(items),
+            ..self
+        }
+    }
+
+    /// Sets the target false-positive probability.
+    pub fn fpr(self, fpr: f64) -> Self {
+        Self {
+            fpr: Some(fpr),
+            ..self
+        }
+    }
+
+    /// Computes the unknown parameters. When the number of hash values is unknown, the optimal one is used,
+    /// rounded to an integer, and the bits or the items are adjusted to keep the target false-positive probability.
+    /// So any two of the bits, items and false-positive probability are enough. When it is known, three
+    /// parameters have to be known. The false-positive probability of the solution is the asymptotic one.
+    pub fn solve(self) -> Result<Solution, Error> {
+        self.validate()?;
+
+        let (bits, hashes, items) = match (self.bits, self.hashes, self.items, self.fpr) {
+            (Some(m), Some(k), Some(n), _) => (m, k, n),
+            (None, Some(k), Some(n), Some(p)) => (min_bits(k, n, p), k, n),
+            (Some(m), None, Some(n), _) => (m, optimal_hashes(m, n), n),
+            (Some(m), Some(k), None, Some(p)) => (m, k, max_items(m, k, p)),
+            (None, None, Some(n), Some(p)) => {
+                let k = optimal_hashes_for_fpr(p);
+                (min_bits(k, n, p), k, n)
+            }
+            (Some(m), None, None, Some(p)) => {
+                let k = optimal_hashes_for_fpr(p);
+                (m, k, max_items(m, k, p))
+            }
+            _ => return Err(Error::UnderdeterminedParams),
+        };
+
+        let solution = Solution {
+            bits,
+            hashes,
+            items,
+            fpr: false_positive_rate(bits, hashes, items),
+        };
+
+        match self.fpr {
+            Some(p) if solution.fpr > p => Err(Error::UnsatisfiableParams),
+            _ => Ok(solution),
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.bits == Some(0) {
+            return Err(Error::ZeroSize("number of bits"));
+        }
+
+        if self.hashes == Some(0) {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        if self.items == Some(0) {
+            return Err(Error::ZeroSize("number of items"));
+        }
+
+        match self.fpr {
+            Some(p) if !(p > 0.0 && p < 1.0) => Err(Error::InvalidProbability(p)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The solution computed by [`Params::solve`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Solution {
+    /// The number of bits of the filter.
+    pub bits: usize,
+    /// The number of hash values computed for each item.
+    pub hashes: usize,
+    /// The number of expected items.
+    pub items: usize,
+    /// The asymptotic false-positive probability after inserting the expected items.
+    pub fpr: f64,
+}
+
+impl Solution {
+    /// The non-asymptotic false-positive probability after inserting the expected items.
+    pub fn exact_fpr(&self) -> f64 {
+        exact_false_positive_rate(self.bits, self.hashes, self.items)
+    }
+
+    /// The number of 64 bits words needed to store the bits, that is the `K` argument of [`crate::BloomFilter`].
+    pub fn cells(&self) -> usize {
+        cells_for_bits(self.bits)
+    }
+
+    /// Returns the solution with the number of bits rounded up to a whole number of 64 bits words,
+    /// which is the number of bits of the matching [`crate::BloomFilter`].
+    pub fn rounded(&self) -> Self {
+        let bits = self.cells() * u64::BITS as usize;
+
+        Self {
+            bits,
+            fpr: false_positive_rate(bits, self.hashes, self.items),
+            ..*self
+        }
+    }
+
+    /// Returns the [`crate::BloomFilter`] type with the `K` and `H` arguments matching the solution.
+    pub fn type_params(&self) -> String {
+        format!("BloomFilter<_, _, {}, {}>", self.cells(), self.hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solve_any_unknown() {
+        let s = Params::new().items(1000).fpr(0.01).solve().unwrap();
+        assert_eq!((s.bits, s.hashes, s.items), (9593, 7, 1000));
+
+        let s = Params::new()
+            .hashes(7)
+            .items(1000)
+            .fpr(0.01)
+            .solve()
+            .unwrap();
+        assert_eq!(s.bits, 9593);
+
+        let s = Params::new()
+            .bits(9593)
+            .hashes(7)
+            .fpr(0.01)
+            .solve()
+            .unwrap();
+        assert_eq!(s.items, 1000);
+
+        let s = Params::new()
+            .bits(9593)
+            .hashes(7)
+            .items(1000)
+            .solve()
+            .unwrap();
+        assert!(s.fpr < 0.01);
+        assert!((s.exact_fpr() - s.fpr).abs() < 1e-5);
+    }
+
+    #[test]
+    fn solve_errors() {
+        let res = Params::new().hashes(7).fpr(0.01).solve();
+        assert_eq!(res, Err(Error::UnderdeterminedParams));
+
+        let res = Params::new().bits(100).items(1000).fpr(0.01).solve();
+        assert_eq!(res, Err(Error::UnsatisfiableParams));
+    }
+
+    #[test]
+    fn estimated() {
+        assert_eq!(estimated_items(1000, 3, 0), 0.0);
+        assert!(estimated_items(1000, 3, 1000).is_infinite());
+
+        // 100 items with 3 hashes set on average 1000 * (1 - e^(-0.3)) bits.
+        let ones = (1000.0 * (1.0 - (-0.3f64).exp())).round() as usize;
+        assert!((estimated_items(1000, 3, ones) - 100.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn blocked() {
+        let classic = false_positive_rate(512 * 19, 7, 1000);
+        let blocked = blocked_false_positive_rate(19, 512, 7, 1000);
+        assert!(blocked > classic);
+        assert!(blocked < 1.5 * classic);
+
+        // The blocked layout is penalized more at lower false-positive rates.
+        let classic = false_positive_rate(512 * 100, 7, 1000);
+        let blocked = blocked_false_positive_rate(100, 512, 7, 1000);
+        assert!(blocked > 2.0 * classic);
+    }
+
+    #[test]
+    fn stable() {
+        let decrements = stable_decrements(1000, 3, 3, 0.01);
+        assert!(stable_false_positive_rate(1000, 3, 3, decrements) <= 0.01);
+        assert!(stable_false_positive_rate(1000, 3, 3, decrements - 1) > 0.01);
+    }
+
+    #[test]
+    fn rounded() {
+        let s = Params::new().bits(100).hashes(3).items(10).solve().unwrap();
+        let r = s.rounded();
+
+        assert_eq!(r.bits % u64::BITS as usize, 0);
+        assert!(r.fpr < s.fpr);
+        assert_eq!(
+            r.type_params(),
+            format!("BloomFilter<_, _, {}, 3>", r.cells())
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{bit_index, params, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::vec::BitVec;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Implements the partitioned bloom filter, where the bits are split into `hashes` equal slices
+/// and the i-th hash value of an item only sets a bit in the i-th slice. The hash values of an item
+/// never select the same bit, so the false-positive probability `(1 - (1 - 1/s)^n)^k` of a filter with
+/// `k` slices of `s` bits does not depend on how the hash values of an item collide with each other.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::PartitionedBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = PartitionedBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+///
+/// filter.insert("Hello world!");
+/// assert!(filter.contains("Hello world!"));
+///```
+pub struct PartitionedBloomFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    bits: BitVec,
+    slice_len: usize,
+    hashes: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> PartitionedBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+{
+    /// Creates a new [`PartitionedBloomFilter`] instance with `hashes` slices of `slice_len` bits each.
+    pub fn new(slice_len: usize, hashes: usize, builder: B) -> Result<Self, Error> {
+        if slice_len == 0 {
+            return Err(Error::ZeroSize("number of bits"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        let len = slice_len
+            .checked_mul(hashes)
+            .ok_or(Error::TooLarge("number of bits"))?;
+
+        Ok(Self {
+            builder,
+            bits: BitVec::repeat(false, len),
+            slice_len,
+            hashes,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`PartitionedBloomFilter`] instance sized to hold `items` items while keeping
+    /// the false-positive probability under `fpr`.
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        if items == 0 {
+            return Err(Error::ZeroSize("number of items"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        // Each slice is a filter with a single hash value and a false-positive probability `q = p^(1/k)`,
+        // so `1 - (1 - 1/s)^n = q` gives the number of bits `s` of a slice.
+        let hashes = params::optimal_hashes_for_fpr(fpr);
+        let q = fpr.powf(1.0 / hashes as f64);
+        let slice_len = (-1.0 / ((-q).ln_1p() / items as f64).exp_m1()).ceil() as usize;
+
+        Self::new(slice_len, hashes, builder)
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn bit_len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Returns the number of bits in each slice.
+    pub fn slice_len(&self) -> usize {
+        self.slice_len
+    }
+
+    /// Returns the number of hash values computed for each item, which is also the number of slices.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the false-positive probability after inserting `items` items.
+    pub fn false_positive_rate(&self, items: usize) -> f64 {
+        params::exact_false_positive_rate(self.slice_len, 1, items).powi(self.hashes as i32)
+    }
+}
+
+impl<T, B> PartitionedBloomFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let slice_len = self.slice_len;
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .enumerate()
+            .for_each(|(slice, hash)| {
+                let index = slice * slice_len + bit_index(hash, slice_len);
+                self.bits.set(index, true)
+            });
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .enumerate()
+            .all(|(slice, hash)| {
+                self.bits[slice * self.slice_len + bit_index(hash, self.slice_len)]
+            })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`PartitionedBloomFilter`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        slice_len: usize,
+        hashes: usize,
+        bits: Bytes,
+    }
+
+    impl<T, B> Serialize for PartitionedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                slice_len: self.slice_len,
+                hashes: self.hashes,
+                bits: Bytes::from_bits(&self.bits),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for PartitionedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> PartitionedBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            let bits = repr
+                .bits
+                .into_bits(repr.slice_len.saturating_mul(repr.hashes))?;
+            let mut filter = Self::new(repr.slice_len, repr.hashes, builder)?;
+            filter.bits = bits;
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn one_bit_per_slice() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = PartitionedBloomFilter::<str, _>::new(100, 4, builder).unwrap();
+
+        filter.insert("Hello world!");
+
+        assert!(filter.contains("Hello world!"));
+        assert!(filter
+            .bits
+            .chunks(filter.slice_len())
+            .all(|slice| slice.count_ones() == 1));
+    }
+
+    #[test]
+    fn too_large() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = PartitionedBloomFilter::<str, _>::new(usize::MAX / 2, 3, builder);
+        assert!(matches!(res, Err(Error::TooLarge("number of bits"))));
+    }
+
+    #[test]
+    fn with_capacity_and_fpr() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter =
+            PartitionedBloomFilter::<u32, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+
+        assert!(filter.false_positive_rate(1000) <= 0.01);
+
+        (0..1000u32).for_each(|i| filter.insert(&i));
+        let false_positives = (1000..11_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 150);
+    }
+}
@@ -0,0 +1,653 @@
+use crate::{builder_fingerprint, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::{field::BitField, vec::BitVec};
+use std::{
+    borrow::Borrow,
+    collections::VecDeque,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The load factor used to size a filter from its capacity.
+const TARGET_LOAD_FACTOR: f64 = 0.75;
+
+/// Implements the [quotient filter](https://doi.org/10.14778/2350229.2350275) of Bender et al.
+/// Each item is reduced to a fingerprint of `q + r` bits. The first `q` bits, the quotient, select the canonical
+/// slot of the fingerprint, and the last `r` bits, the remainder, are stored in that slot or, when it is taken,
+/// in one of the slots which follow it. Three metadata bits for each slot keep track of these shifts.
+///
+/// Since the fingerprints can be recovered from the filter, two filters can be merged and a filter can double its
+/// number of slots, by moving one bit from the remainder to the quotient, without hashing the items again.
+/// The filter stores a multiset of fingerprints: inserting an item twice stores two copies, and removing it
+/// deletes one copy, so removing an item never removes another item with the same fingerprint.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::QuotientFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = QuotientFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+///
+/// filter.insert("Hello world!").unwrap();
+/// assert!(filter.contains("Hello world!"));
+///
+/// filter.grow().unwrap();
+/// assert!(filter.contains("Hello world!"));
+///
+/// assert!(filter.remove("Hello world!"));
+/// assert!(!filter.contains("Hello world!"));
+///```
+pub struct QuotientFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    occupied: BitVec,
+    continuation: BitVec,
+    shifted: BitVec,
+    remainders: BitVec,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> QuotientFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`QuotientFilter`] instance with `2^quotient_bits` slots, each storing a remainder
+    /// of `remainder_bits` bits. The fingerprints have `quotient_bits + remainder_bits` bits, up to 64.
+    pub fn new(quotient_bits: u32, remainder_bits: u32, builder: B) -> Result<Self, Error> {
+        if quotient_bits == 0 || quotient_bits >= usize::BITS {
+            return Err(Error::InvalidFingerprintBits(quotient_bits));
+        }
+
+        if remainder_bits == 0 || quotient_bits + remainder_bits > u64::BITS {
+            return Err(Error::InvalidFingerprintBits(remainder_bits));
+        }
+
+        let slots = 1 << quotient_bits;
+
+        Ok(Self {
+            builder,
+            quotient_bits,
+            remainder_bits,
+            occupied: BitVec::repeat(false, slots),
+            continuation: BitVec::repeat(false, slots),
+            shifted: BitVec::repeat(false, slots),
+            remainders: BitVec::repeat(false, slots * remainder_bits as usize),
+            len: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`QuotientFilter`] instance which holds `capacity` items with a load factor of 75%,
+    /// with remainders long enough to keep the false-positive probability under `fpr`.
+    pub fn with_capacity_and_fpr(capacity: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(Error::ZeroSize("capacity"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        let slots = (capacity as f64 / TARGET_LOAD_FACTOR).ceil() as usize;
+        let quotient_bits = slots.next_power_of_two().trailing_zeros().max(1);
+
+        // At load factor `a`, the false-positive probability is about `a 2^-r`.
+        let remainder_bits = (TARGET_LOAD_FACTOR / fpr).log2().ceil().max(1.0) as u32;
+
+        Self::new(quotient_bits, remainder_bits, builder)
+    }
+
+    /// Returns the number of items in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the filter has no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots in the filter.
+    pub fn slots(&self) -> usize {
+        self.occupied.len()
+    }
+
+    /// Returns the number of bits of the quotient.
+    pub fn quotient_bits(&self) -> u32 {
+        self.quotient_bits
+    }
+
+    /// Returns the number of bits of the remainder.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    /// Returns the fraction of the slots which store a remainder.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots() as f64
+    }
+
+    /// Returns the false-positive probability `1 - e^(-a 2^-r)` for the current load factor `a`.
+    pub fn false_positive_rate(&self) -> f64 {
+        -(-self.load_factor() * 0.5f64.powi(self.remainder_bits as i32)).exp_m1()
+    }
+
+    /// Returns the fingerprints stored in the filter, in ascending order.
+    ///
+    /// # Example
+    ///
+    ///```
+    /// use aabel_bloom_rs::QuotientFilter;
+    /// use aabel_multihash_rs::BuildPairHasher;
+    ///
+    /// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+    /// let mut filter = QuotientFilter::<u32, _>::new(8, 8, builder).unwrap();
+    ///
+    /// (0..100u32).for_each(|i| filter.insert(&i).unwrap());
+    ///
+    /// let fingerprints = filter.fingerprints().collect::<Vec<_>>();
+    /// assert_eq!(fingerprints.len(), 100);
+    /// assert!(fingerprints.windows(2).all(|w| w[0] <= w[1]));
+    ///```
+    pub fn fingerprints(&self) -> impl Iterator<Item = u64> {
+        let mut fingerprints = self
+            .entries()
+            .into_iter()
+            .map(|(quotient, remainder)| self.fingerprint(quotient, remainder))
+            .collect::<Vec<_>>();
+
+        fingerprints.sort_unstable();
+        fingerprints.into_iter()
+    }
+
+    /// Doubles the number of slots of the filter by moving one bit from the remainder to the quotient.
+    /// The false-positive probability stays the same, since the load factor is halved while the remainders
+    /// are one bit shorter. It fails when the remainders have a single bit.
+    pub fn grow(&mut self) -> Result<(), Error> {
+        if self.remainder_bits == 1 || self.quotient_bits + 1 >= usize::BITS {
+            return Err(Error::InvalidFingerprintBits(self.remainder_bits - 1));
+        }
+
+        let fingerprints = self.fingerprints().collect::<Vec<_>>();
+        self.reset(self.quotient_bits + 1, self.remainder_bits - 1);
+        fingerprints
+            .into_iter()
+            .try_for_each(|fingerprint| self.insert_fingerprint(fingerprint))
+    }
+
+    /// Adds to the filter the items of another filter, growing the filter when needed to keep its load factor
+    /// under 75%. It fails when the fingerprints of the two filters have different lengths or when the filters
+    /// are built with different hasher builders.
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        if self.quotient_bits + self.remainder_bits != other.quotient_bits + other.remainder_bits {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        while (self.len + other.len) as f64 > TARGET_LOAD_FACTOR * self.slots() as f64 {
+            self.grow()?;
+        }
+
+        other
+            .fingerprints()
+            .try_for_each(|fingerprint| self.insert_fingerprint(fingerprint))
+    }
+
+    fn reset(&mut self, quotient_bits: u32, remainder_bits: u32) {
+        let slots = 1 << quotient_bits;
+
+        self.quotient_bits = quotient_bits;
+        self.remainder_bits = remainder_bits;
+        self.occupied = BitVec::repeat(false, slots);
+        self.continuation = BitVec::repeat(false, slots);
+        self.shifted = BitVec::repeat(false, slots);
+        self.remainders = BitVec::repeat(false, slots * remainder_bits as usize);
+        self.len = 0;
+    }
+
+    fn fingerprint(&self, quotient: usize, remainder: u64) -> u64 {
+        ((quotient as u64) << self.remainder_bits) | remainder
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let quotient = (fingerprint >> self.remainder_bits) as usize;
+        let remainder = fingerprint & (u64::MAX >> (u64::BITS - self.remainder_bits));
+        (quotient, remainder)
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots() - 1)
+    }
+
+    fn prev(&self, slot: usize) -> usize {
+        slot.wrapping_sub(1) & (self.slots() - 1)
+    }
+
+    /// The distance from `from` to `to`, going forward around the table.
+    fn distance(&self, from: usize, to: usize) -> usize {
+        to.wrapping_sub(from) & (self.slots() - 1)
+    }
+
+    fn is_empty_slot(&self, slot: usize) -> bool {
+        !self.occupied[slot] && !self.continuation[slot] && !self.shifted[slot]
+    }
+
+    fn remainder(&self, slot: usize) -> u64 {
+        let bits = self.remainder_bits as usize;
+        self.remainders[slot * bits..(slot + 1) * bits].load_le()
+    }
+
+    fn set_remainder(&mut self, slot: usize, remainder: u64) {
+        let bits = self.remainder_bits as usize;
+        self.remainders[slot * bits..(slot + 1) * bits].store_le(remainder);
+    }
+
+    /// Finds the slot where the run of a given occupied quotient starts.
+    fn run_start(&self, quotient: usize) -> usize {
+        // Walk back to the start of the cluster, which is the first slot holding a remainder in its canonical slot.
+        let mut canonical = quotient;
+        while self.shifted[canonical] {
+            canonical = self.prev(canonical);
+        }
+
+        // Walk forward in lockstep over the occupied quotients and their runs.
+        let mut slot = canonical;
+        while canonical != quotient {
+            loop {
+                slot = self.next(slot);
+                if !self.continuation[slot] {
+                    break;
+                }
+            }
+
+            loop {
+                canonical = self.next(canonical);
+                if self.occupied[canonical] {
+                    break;
+                }
+            }
+        }
+
+        slot
+    }
+
+    /// Finds the first slot of the sequence of non-empty slots which contains a given non-empty slot.
+    fn region_start(&self, slot: usize) -> usize {
+        let mut start = slot;
+        while !self.is_empty_slot(self.prev(start)) {
+            start = self.prev(start);
+        }
+
+        start
+    }
+
+    /// Decodes the quotients and remainders stored in the sequence of non-empty slots starting at `start`.
+    fn decode(&self, start: usize) -> Vec<(usize, u64)> {
+        let mut quotients = VecDeque::new();
+        let mut quotient = start;
+        let mut entries = Vec::new();
+
+        let mut slot = start;
+        while !self.is_empty_slot(slot) && entries.len() < self.slots() {
+            if self.occupied[slot] {
+                quotients.push_back(slot);
+            }
+
+            if !self.continuation[slot] {
+                quotient = quotients.pop_front().unwrap_or(quotient);
+            }
+
+            entries.push((quotient, self.remainder(slot)));
+            slot = self.next(slot);
+        }
+
+        entries
+    }
+
+    /// Stores, starting at the empty slot `start`, the quotients and remainders sorted by the distance
+    /// of the quotient from `start` and then by the remainder.
+    fn encode(&mut self, start: usize, entries: &[(usize, u64)]) {
+        let mut offset = 0;
+        let mut previous = None;
+
+        for &(quotient, remainder) in entries {
+            let is_continuation = previous == Some(quotient);
+            if !is_continuation {
+                offset = offset.max(self.distance(start, quotient));
+                self.occupied.set(quotient, true);
+            }
+
+            let slot = (start + offset) & (self.slots() - 1);
+            self.set_remainder(slot, remainder);
+            self.continuation.set(slot, is_continuation);
+            self.shifted.set(slot, slot != quotient);
+
+            previous = Some(quotient);
+            offset += 1;
+        }
+    }
+
+    fn clear(&mut self, start: usize, len: usize) {
+        let mask = self.slots() - 1;
+
+        (0..len)
+            .map(|offset| (start + offset) & mask)
+            .for_each(|slot| {
+                self.occupied.set(slot, false);
+                self.continuation.set(slot, false);
+                self.shifted.set(slot, false);
+                self.set_remainder(slot, 0);
+            });
+    }
+
+    /// Decodes all the quotients and remainders stored in the filter.
+    fn entries(&self) -> Vec<(usize, u64)> {
+        let Some(empty) = (0..self.slots()).find(|slot| self.is_empty_slot(*slot)) else {
+            return Vec::new();
+        };
+
+        let mut entries = Vec::with_capacity(self.len);
+        let mut slot = self.next(empty);
+        while slot != empty {
+            if self.is_empty_slot(slot) {
+                slot = self.next(slot);
+            } else {
+                let region = self.decode(slot);
+                slot = (slot + region.len()) & (self.slots() - 1);
+                entries.extend(region);
+            }
+        }
+
+        entries
+    }
+
+    fn insert_fingerprint(&mut self, fingerprint: u64) -> Result<(), Error> {
+        // Keep an empty slot, so each sequence of non-empty slots has a start.
+        if self.len + 1 >= self.slots() {
+            return Err(Error::Full);
+        }
+
+        let (quotient, remainder) = self.split(fingerprint);
+
+        if self.is_empty_slot(quotient) {
+            self.occupied.set(quotient, true);
+            self.set_remainder(quotient, remainder);
+        } else {
+            let start = self.region_start(quotient);
+            let mut entries = self.decode(start);
+            let key = |&(q, r): &(usize, u64)| (self.distance(start, q), r);
+            let index = entries.partition_point(|entry| key(entry) <= key(&(quotient, remainder)));
+
+            entries.insert(index, (quotient, remainder));
+            self.clear(start, entries.len() - 1);
+            self.encode(start, &entries);
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    fn remove_fingerprint(&mut self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if !self.occupied[quotient] {
+            return false;
+        }
+
+        let start = self.region_start(quotient);
+        let mut entries = self.decode(start);
+        let Some(index) = entries
+            .iter()
+            .position(|entry| *entry == (quotient, remainder))
+        else {
+            return false;
+        };
+
+        entries.remove(index);
+        self.clear(start, entries.len() + 1);
+        self.encode(start, &entries);
+
+        self.len -= 1;
+        true
+    }
+
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if !self.occupied[quotient] {
+            return false;
+        }
+
+        // The remainders of a run are sorted.
+        let mut slot = self.run_start(quotient);
+        loop {
+            match self.remainder(slot) {
+                r if r == remainder => return true,
+                r if r > remainder => return false,
+                _ => slot = self.next(slot),
+            }
+
+            if !self.continuation[slot] {
+                return false;
+            }
+        }
+    }
+}
+
+impl<T, B> QuotientFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item. It returns [`Error::Full`] when all the slots but one are taken.
+    pub fn insert<U>(&mut self, item: &U) -> Result<(), Error>
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let fingerprint = self.item_fingerprint(item);
+        self.insert_fingerprint(fingerprint)
+    }
+
+    /// Removes an item from the filter. It returns false when the item is not present in the filter.
+    pub fn remove<U>(&mut self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let fingerprint = self.item_fingerprint(item);
+        self.remove_fingerprint(fingerprint)
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let fingerprint = self.item_fingerprint(item);
+        self.contains_fingerprint(fingerprint)
+    }
+
+    fn item_fingerprint<U>(&self, item: &U) -> u64
+    where
+        U: Hash + ?Sized,
+    {
+        let hash: u64 = self
+            .builder
+            .hashes_one(item)
+            .next()
+            .expect("the hash sequence is infinite")
+            .into();
+
+        hash >> (u64::BITS - self.quotient_bits - self.remainder_bits)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`QuotientFilter`], with its fingerprints in ascending order. The slots are
+    /// rebuilt by inserting the fingerprints again, so a corrupted input cannot break the layout of the slots.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        quotient_bits: u32,
+        remainder_bits: u32,
+        len: usize,
+        fingerprints: Bytes,
+    }
+
+    impl<T, B> Serialize for QuotientFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                quotient_bits: self.quotient_bits,
+                remainder_bits: self.remainder_bits,
+                len: self.len,
+                fingerprints: Bytes::from_values(
+                    self.fingerprints(),
+                    self.quotient_bits + self.remainder_bits,
+                ),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for QuotientFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> QuotientFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            let mut filter = Self::new(repr.quotient_bits, repr.remainder_bits, builder)?;
+            if repr.len >= filter.slots() {
+                return Err(Error::InvalidFormat("number of items"));
+            }
+
+            repr.fingerprints
+                .into_values(repr.len, repr.quotient_bits + repr.remainder_bits)?
+                .into_iter()
+                .try_for_each(|fingerprint| filter.insert_fingerprint(fingerprint))?;
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn insert_remove_contains() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = QuotientFilter::<u32, _>::new(10, 10, builder).unwrap();
+
+        (0..900u32).for_each(|i| filter.insert(&i).unwrap());
+        assert_eq!(filter.len(), 900);
+        assert!((0..900u32).all(|i| filter.contains(&i)));
+
+        assert!((0..900u32).step_by(2).all(|i| filter.remove(&i)));
+        assert!((1..900u32).step_by(2).all(|i| filter.contains(&i)));
+        assert_eq!(filter.len(), 450);
+
+        let mut fingerprints = filter.fingerprints().collect::<Vec<_>>();
+        fingerprints.dedup();
+        assert_eq!(fingerprints.len(), 450);
+    }
+
+    #[test]
+    fn duplicates() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = QuotientFilter::<u32, _>::new(4, 4, builder).unwrap();
+
+        filter.insert(&1).unwrap();
+        filter.insert(&1).unwrap();
+
+        assert!(filter.remove(&1));
+        assert!(filter.contains(&1));
+        assert!(filter.remove(&1));
+        assert!(!filter.contains(&1));
+        assert!(!filter.remove(&1));
+    }
+
+    #[test]
+    fn full() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = QuotientFilter::<u32, _>::new(4, 4, builder).unwrap();
+
+        (0..15u32).for_each(|i| filter.insert(&i).unwrap());
+        assert_eq!(filter.insert(&15), Err(Error::Full));
+        assert!((0..15u32).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn grow_merge() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter1 = QuotientFilter::<u32, _>::new(8, 12, builder()).unwrap();
+        let mut filter2 = QuotientFilter::<u32, _>::new(9, 11, builder()).unwrap();
+
+        (0..150u32).for_each(|i| filter1.insert(&i).unwrap());
+        (100..300u32).for_each(|i| filter2.insert(&i).unwrap());
+
+        filter1.grow().unwrap();
+        assert_eq!(filter1.slots(), 512);
+        assert!((0..150u32).all(|i| filter1.contains(&i)));
+
+        filter1.merge(&filter2).unwrap();
+        assert_eq!(filter1.len(), 350);
+        assert!(filter1.load_factor() <= 0.75);
+        assert!((0..300u32).all(|i| filter1.contains(&i)));
+
+        let other = QuotientFilter::<u32, _>::new(8, 8, builder()).unwrap();
+        assert_eq!(filter1.merge(&other), Err(Error::IncompatibleSizes));
+    }
+}
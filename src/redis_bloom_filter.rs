@@ -0,0 +1,613 @@
+use crate::{
+    murmur2::{murmur2, murmur64a},
+    Error,
+};
+use bitvec::vec::BitVec;
+
+/// The largest chunk of bits returned by `BF.SCANDUMP`.
+pub const REDIS_MAX_CHUNK: usize = 16 * 1024 * 1024;
+
+/// The default expansion of `BF.RESERVE`, the growth factor of the capacity of the links.
+pub const REDIS_DEFAULT_EXPANSION: u32 = 2;
+
+/// The options of a chain, stored in its header.
+const OPT_NOROUND: u32 = 1;
+const OPT_ENTS_IS_BITS: u32 = 2;
+const OPT_FORCE64: u32 = 4;
+const OPT_NO_SCALING: u32 = 8;
+
+/// The ratio by which the false-positive probability of each link is tightened.
+const TIGHTENING: f64 = 0.5;
+
+/// The constants `ln(2)` and `ln(2)^2` of libbloom, which RedisBloom uses to size the links. They are truncated,
+/// and kept that way so the number of hash values of a link does not differ from RedisBloom at a rounding boundary.
+#[allow(clippy::approx_constant)]
+const LN2: f64 = 0.693147180559945;
+const LN2_SQUARED: f64 = 0.480453013918201;
+
+/// The seeds of the two hash values of an item.
+const SEED32: u32 = 0x9747_b28c;
+const SEED64: u64 = 0xc6a4_a793_5bd1_e995;
+
+/// The packed lengths of the header of a chain and of the description of each link.
+const HEADER_LEN: usize = 20;
+const LINK_LEN: usize = 53;
+
+/// One of the links of a [`RedisBloomFilter`], the `struct bloom` of RedisBloom.
+#[derive(Debug, Clone, PartialEq)]
+struct Link {
+    bf: BitVec<u8>,
+    bits: u64,
+    size: u64,
+    error: f64,
+    bpe: f64,
+    hashes: u32,
+    entries: u64,
+    n2: u8,
+}
+
+impl Link {
+    /// Creates a link sized like `bloom_init` sizes it for `entries` items and a false-positive probability `error`.
+    fn new(entries: u64, error: f64, options: u32) -> Result<Self, Error> {
+        if entries == 0 {
+            return Err(Error::ZeroSize("capacity"));
+        }
+
+        if !(error > 0.0 && error < 1.0) {
+            return Err(Error::InvalidProbability(error));
+        }
+
+        if options & OPT_ENTS_IS_BITS != 0 {
+            return Err(Error::InvalidFormat("options"));
+        }
+
+        let bpe = -(error.ln() / LN2_SQUARED);
+        let ideal = entries as f64 * bpe;
+        if ideal < 1.0 {
+            return Err(Error::ZeroSize("number of bits"));
+        }
+
+        let (bits, n2, entries) = if options & OPT_NOROUND != 0 {
+            (ideal as u64, 0, entries)
+        } else {
+            // The exponent of the ideal number of bits, like `logb`.
+            let exponent = ((ideal.to_bits() >> 52) & 0x7ff) as u32 - 1023;
+            if exponent >= 63 {
+                return Err(Error::TooLarge("number of bits"));
+            }
+
+            // The extra bits of the power of two hold more items.
+            let bits = 1u64 << (exponent + 1);
+            let extra = ((bits as f64 - ideal) as u64 as f64 / bpe) as u64;
+            (bits, exponent as u8 + 1, entries + extra)
+        };
+
+        let bytes = bits.div_ceil(64) * 8;
+        let len = usize::try_from(bytes * 8).map_err(|_| Error::TooLarge("number of bits"))?;
+
+        Ok(Self {
+            bf: BitVec::repeat(false, len),
+            bits: bytes * 8,
+            size: 0,
+            error,
+            bpe,
+            hashes: (LN2 * bpe).ceil() as u32,
+            entries,
+            n2,
+        })
+    }
+
+    fn bytes(&self) -> usize {
+        self.bf.len() / 8
+    }
+
+    fn bit_index(&self, (a, b): (u64, u64), i: u64) -> usize {
+        let modulus = if self.n2 > 0 { 1 << self.n2 } else { self.bits };
+        (a.wrapping_add(i.wrapping_mul(b)) % modulus) as usize
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        (0..self.hashes as u64).for_each(|i| {
+            let index = self.bit_index(hash, i);
+            self.bf.set(index, true);
+        });
+        self.size += 1;
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        (0..self.hashes as u64).all(|i| self.bf[self.bit_index(hash, i)])
+    }
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.bytes() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.bits.to_le_bytes());
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.error.to_le_bytes());
+        bytes.extend_from_slice(&self.bpe.to_le_bytes());
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        bytes.extend_from_slice(&self.entries.to_le_bytes());
+        bytes.push(self.n2);
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let len = u64_at(0)
+            .checked_mul(8)
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or(Error::InvalidFormat("link"))?;
+
+        let link = Self {
+            bf: BitVec::new(),
+            bits: u64_at(8),
+            size: u64_at(16),
+            error: f64::from_bits(u64_at(24)),
+            bpe: f64::from_bits(u64_at(32)),
+            hashes: u32::from_le_bytes(bytes[40..44].try_into().unwrap()),
+            entries: u64_at(44),
+            n2: bytes[52],
+        };
+
+        let modulus = if link.n2 > 0 { 1 << link.n2 } else { link.bits };
+        if len == 0 || link.hashes == 0 || link.n2 > 63 || modulus == 0 || modulus > len as u64 {
+            return Err(Error::InvalidFormat("link"));
+        }
+
+        Ok(Self {
+            bf: BitVec::repeat(false, len),
+            ..link
+        })
+    }
+}
+
+/// Implements the scalable bloom filter of [RedisBloom](https://github.com/RedisBloom/RedisBloom), the chain of
+/// links behind the `BF.*` commands, so filters can be migrated out of Redis and back. [`RedisBloomFilter::from_chunks`]
+/// rebuilds a filter from the chunks returned by `BF.SCANDUMP`, and [`RedisBloomFilter::to_chunks`] produces the chunks
+/// expected by `BF.LOADCHUNK`.
+///
+/// The items are byte strings, the arguments of `BF.ADD` and `BF.EXISTS`, hashed twice with MurmurHash64A and mapped
+/// to the bits with double hashing, so [`RedisBloomFilter::contains`] answers like `BF.EXISTS`. Each link is sized
+/// like RedisBloom sizes it, and a new link is added when the last one holds as many items as its capacity.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::RedisBloomFilter;
+///
+/// let mut filter = RedisBloomFilter::new(100, 0.01).unwrap();
+/// assert!(filter.insert("Hello world!").unwrap());
+/// assert!(!filter.insert("Hello world!").unwrap());
+///
+/// let chunks = filter.to_chunks();
+/// let filter = RedisBloomFilter::from_chunks(chunks).unwrap();
+/// assert!(filter.contains("Hello world!"));
+///```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisBloomFilter {
+    links: Vec<Link>,
+    size: u64,
+    options: u32,
+    growth: u32,
+}
+
+impl RedisBloomFilter {
+    /// Creates a new [`RedisBloomFilter`] instance like `BF.RESERVE` with a given capacity and false-positive
+    /// probability, using the [`REDIS_DEFAULT_EXPANSION`].
+    pub fn new(capacity: usize, fpr: f64) -> Result<Self, Error> {
+        Self::with_expansion(capacity, fpr, REDIS_DEFAULT_EXPANSION)
+    }
+
+    /// Creates a new [`RedisBloomFilter`] instance like `BF.RESERVE` with a given capacity, false-positive
+    /// probability and `EXPANSION` argument.
+    pub fn with_expansion(capacity: usize, fpr: f64, expansion: u32) -> Result<Self, Error> {
+        if expansion == 0 {
+            return Err(Error::ZeroSize("expansion"));
+        }
+
+        Self::with_options(capacity, fpr, expansion, OPT_FORCE64 | OPT_NOROUND)
+    }
+
+    /// Creates a new [`RedisBloomFilter`] instance like `BF.RESERVE` with the `NONSCALING` argument,
+    /// which fails to insert new items once the capacity is reached.
+    pub fn non_scaling(capacity: usize, fpr: f64) -> Result<Self, Error> {
+        Self::with_options(
+            capacity,
+            fpr,
+            REDIS_DEFAULT_EXPANSION,
+            OPT_FORCE64 | OPT_NOROUND | OPT_NO_SCALING,
+        )
+    }
+
+    fn with_options(capacity: usize, fpr: f64, growth: u32, options: u32) -> Result<Self, Error> {
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        let tightening = if options & OPT_NO_SCALING != 0 {
+            1.0
+        } else {
+            TIGHTENING
+        };
+
+        Ok(Self {
+            links: vec![Link::new(capacity as u64, fpr * tightening, options)?],
+            size: 0,
+            options,
+            growth,
+        })
+    }
+
+    /// Returns the number of distinct items inserted in the filter.
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Returns true when no item was inserted in the filter.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the number of links.
+    pub fn links(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns the total capacity of the links, like the `Capacity` field of `BF.INFO`.
+    pub fn capacity(&self) -> usize {
+        self.links.iter().map(|link| link.entries as usize).sum()
+    }
+
+    /// Returns the total number of bits of the links.
+    pub fn bit_len(&self) -> usize {
+        self.links.iter().map(|link| link.bf.len()).sum()
+    }
+
+    fn hash(&self, item: &[u8]) -> (u64, u64) {
+        if self.options & OPT_FORCE64 != 0 {
+            let a = murmur64a(item, SEED64);
+            (a, murmur64a(item, a))
+        } else {
+            let a = murmur2(item, SEED32);
+            (a as u64, murmur2(item, a) as u64)
+        }
+    }
+
+    /// Inserts in the filter a new item, like `BF.ADD`. It returns false when the item is already present,
+    /// and [`Error::Full`] when a non-scaling filter reached its capacity.
+    pub fn insert<V: AsRef<[u8]> + ?Sized>(&mut self, item: &V) -> Result<bool, Error> {
+        let hash = self.hash(item.as_ref());
+        if self.links.iter().rev().any(|link| link.contains(hash)) {
+            return Ok(false);
+        }
+
+        let last = self.links.last().expect("a filter has at least one link");
+        if last.size >= last.entries {
+            if self.options & OPT_NO_SCALING != 0 {
+                return Err(Error::Full);
+            }
+
+            let entries = last.entries.saturating_mul(self.growth as u64);
+            let link = Link::new(entries, last.error * TIGHTENING, self.options)?;
+            self.links.push(link);
+        }
+
+        self.links
+            .last_mut()
+            .expect("a filter has at least one link")
+            .insert(hash);
+        self.size += 1;
+
+        Ok(true)
+    }
+
+    /// Checks if a given item is present in the filter, like `BF.EXISTS`.
+    pub fn contains<V: AsRef<[u8]> + ?Sized>(&self, item: &V) -> bool {
+        let hash = self.hash(item.as_ref());
+        self.links.iter().rev().any(|link| link.contains(hash))
+    }
+
+    /// Returns the header of the chain, the size, options and growth of the chain and the parameters of each link
+    /// packed in little-endian order, which is the first chunk returned by `BF.SCANDUMP`.
+    pub fn header(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.links.len() * LINK_LEN);
+
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&(self.links.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.options.to_le_bytes());
+        bytes.extend_from_slice(&self.growth.to_le_bytes());
+        self.links.iter().for_each(|link| link.encode(&mut bytes));
+
+        bytes
+    }
+
+    /// Answers `BF.SCANDUMP` with a given iterator: the iterator to pass to the next call and the chunk,
+    /// starting with the header for the zero iterator. The last call returns the zero iterator and no bytes.
+    pub fn scan_dump(&self, iter: i64) -> (i64, Vec<u8>) {
+        self.scan_dump_with(iter, REDIS_MAX_CHUNK)
+    }
+
+    fn scan_dump_with(&self, iter: i64, max_chunk: usize) -> (i64, Vec<u8>) {
+        if iter == 0 {
+            return (1, self.header());
+        }
+
+        match self.link_at(iter - 1) {
+            Some((link, offset)) => {
+                let bytes = &self.links[link].bf.as_raw_slice()[offset..];
+                let chunk = &bytes[..bytes.len().min(max_chunk)];
+                (iter + chunk.len() as i64, chunk.to_vec())
+            }
+            None => (0, Vec::new()),
+        }
+    }
+
+    /// Returns the index of the link holding a position in the concatenated bits of the links, and the offset
+    /// of the position in the bits of that link.
+    fn link_at(&self, position: i64) -> Option<(usize, usize)> {
+        let mut position = usize::try_from(position).ok()?;
+
+        for (index, link) in self.links.iter().enumerate() {
+            if position < link.bytes() {
+                return Some((index, position));
+            }
+
+            position -= link.bytes();
+        }
+
+        None
+    }
+
+    /// Returns the chunks of the filter, the pairs of iterator and bytes returned by successive `BF.SCANDUMP` calls,
+    /// which restore the filter when passed in order to `BF.LOADCHUNK`.
+    pub fn to_chunks(&self) -> Vec<(i64, Vec<u8>)> {
+        let mut chunks = Vec::new();
+        let mut iter = 0;
+
+        loop {
+            let (next, chunk) = self.scan_dump(iter);
+            if next == 0 {
+                return chunks;
+            }
+
+            chunks.push((next, chunk));
+            iter = next;
+        }
+    }
+
+    /// Creates a filter with empty links from the header returned by the first `BF.SCANDUMP` call.
+    pub fn from_header(header: &[u8]) -> Result<Self, Error> {
+        if header.len() < HEADER_LEN {
+            return Err(Error::InvalidFormat("header length"));
+        }
+
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let links = u32_at(8) as usize;
+        if links == 0 {
+            return Err(Error::InvalidFormat("number of links"));
+        }
+
+        if header.len() != HEADER_LEN + links * LINK_LEN {
+            return Err(Error::InvalidFormat("header length"));
+        }
+
+        let links = header[HEADER_LEN..]
+            .chunks_exact(LINK_LEN)
+            .map(Link::decode)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            links,
+            size: u64::from_le_bytes(header[..8].try_into().unwrap()),
+            options: u32_at(12),
+            growth: u32_at(16),
+        })
+    }
+
+    /// Loads the bits of a chunk returned by `BF.SCANDUMP` with a given iterator, like `BF.LOADCHUNK`.
+    pub fn load_chunk(&mut self, iter: i64, chunk: &[u8]) -> Result<(), Error> {
+        let len = chunk.len() as i64;
+        if chunk.is_empty() || iter <= len {
+            return Err(Error::InvalidFormat("chunk iterator"));
+        }
+
+        let (link, offset) = self
+            .link_at(iter - len - 1)
+            .ok_or(Error::InvalidFormat("chunk offset"))?;
+
+        let bytes = &mut self.links[link].bf.as_raw_mut_slice()[offset..];
+        if chunk.len() > bytes.len() {
+            return Err(Error::InvalidFormat("chunk length"));
+        }
+
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Rebuilds a filter from the chunks returned by successive `BF.SCANDUMP` calls, starting with the header.
+    /// The final empty chunk with the zero iterator may be included.
+    pub fn from_chunks<I, C>(chunks: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (i64, C)>,
+        C: AsRef<[u8]>,
+    {
+        let mut chunks = chunks.into_iter();
+
+        let mut filter = match chunks.next() {
+            Some((1, header)) => Self::from_header(header.as_ref())?,
+            _ => return Err(Error::InvalidFormat("header")),
+        };
+
+        for (iter, chunk) in chunks {
+            if iter == 0 && chunk.as_ref().is_empty() {
+                break;
+            }
+
+            filter.load_chunk(iter, chunk.as_ref())?;
+        }
+
+        Ok(filter)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`RedisBloomFilter`], its header and the bits of each link.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        header: Bytes,
+        links: Vec<Bytes>,
+    }
+
+    impl Serialize for RedisBloomFilter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                header: Bytes(self.header()),
+                links: self
+                    .links
+                    .iter()
+                    .map(|link| Bytes(link.bf.as_raw_slice().to_vec()))
+                    .collect(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RedisBloomFilter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr).map_err(de_error)
+        }
+    }
+
+    impl RedisBloomFilter {
+        fn from_repr(repr: Repr) -> Result<Self, Error> {
+            let mut filter = Self::from_header(&repr.header.0)?;
+            if repr.links.len() != filter.links.len() {
+                return Err(Error::InvalidFormat("number of links"));
+            }
+
+            for (link, bytes) in filter.links.iter_mut().zip(repr.links) {
+                if bytes.0.len() != link.bytes() {
+                    return Err(Error::InvalidFormat("bit array length"));
+                }
+
+                link.bf = BitVec::from_vec(bytes.0);
+            }
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        let filter = RedisBloomFilter::new(1000, 0.01).unwrap();
+        let link = &filter.links[0];
+        assert_eq!(link.error, 0.005);
+        assert_eq!(link.bits, 11_072);
+        assert_eq!(link.bytes(), 1384);
+        assert_eq!(link.hashes, 8);
+        assert_eq!((link.entries, link.n2), (1000, 0));
+
+        // Without the `NOROUND` option of the legacy filters, the bits are rounded up to a power of two.
+        let link = Link::new(1000, 0.005, OPT_FORCE64).unwrap();
+        assert_eq!((link.bits, link.n2, link.entries), (16_384, 14, 1485));
+    }
+
+    #[test]
+    fn scaling() {
+        let mut filter = RedisBloomFilter::new(100, 0.01).unwrap();
+        let added = (0..1000)
+            .filter(|i| filter.insert(&i.to_string()).unwrap())
+            .count();
+        assert!((0..1000).all(|i| filter.contains(&i.to_string())));
+        assert!(!filter.insert("0").unwrap());
+
+        // The false positives are not added, like `BF.ADD` answers 0 for them.
+        assert_eq!(filter.len(), added);
+        assert!(added > 990);
+        assert_eq!(filter.links(), 4);
+        assert_eq!(filter.capacity(), 100 + 200 + 400 + 800);
+        assert_eq!(filter.links[1].error, 0.0025);
+
+        let false_positives = (1000..11_000)
+            .filter(|i| filter.contains(&i.to_string()))
+            .count();
+        assert!(false_positives < 100);
+
+        let mut filter = RedisBloomFilter::non_scaling(10, 0.01).unwrap();
+        (0..10).for_each(|i| assert!(filter.insert(&[i]).unwrap()));
+        assert_eq!(filter.insert(&[10]).err(), Some(Error::Full));
+    }
+
+    #[test]
+    fn header() {
+        let mut filter = RedisBloomFilter::new(1000, 0.01).unwrap();
+        filter.insert("Hello world!").unwrap();
+
+        let header = filter.header();
+        assert_eq!(header.len(), HEADER_LEN + LINK_LEN);
+        assert_eq!(
+            header[..20],
+            [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, 2, 0, 0, 0]
+        );
+        assert_eq!(header[20..28], 1384u64.to_le_bytes());
+        assert_eq!(header[28..36], 11_072u64.to_le_bytes());
+        assert_eq!(header[36..44], 1u64.to_le_bytes());
+        assert_eq!(header[44..52], 0.005f64.to_le_bytes());
+        assert_eq!(header[60..64], 8u32.to_le_bytes());
+        assert_eq!(header[64..72], 1000u64.to_le_bytes());
+        assert_eq!(header[72], 0);
+    }
+
+    #[test]
+    fn scan_dump_load_chunk() {
+        let mut filter = RedisBloomFilter::new(100, 0.01).unwrap();
+        (0..300).for_each(|i| _ = filter.insert(&i.to_string()).unwrap());
+        let bytes: Vec<usize> = filter.links.iter().map(Link::bytes).collect();
+        assert_eq!(bytes, vec![144, 312]);
+
+        // The chunks do not span two links.
+        let mut chunks = Vec::new();
+        let mut iter = 0;
+        loop {
+            let (next, chunk) = filter.scan_dump_with(iter, 100);
+            chunks.push((next, chunk));
+            if next == 0 {
+                break;
+            }
+            iter = next;
+        }
+
+        let iters: Vec<i64> = chunks.iter().map(|(iter, _)| *iter).collect();
+        assert_eq!(iters, vec![1, 101, 145, 245, 345, 445, 457, 0]);
+
+        let other = RedisBloomFilter::from_chunks(chunks.clone()).unwrap();
+        assert_eq!(other, filter);
+        assert_eq!(
+            RedisBloomFilter::from_chunks(filter.to_chunks()).unwrap(),
+            filter
+        );
+
+        let mut other = RedisBloomFilter::from_header(&chunks[0].1).unwrap();
+        assert_eq!(
+            other.load_chunk(457 + 1, &[0]).err(),
+            Some(Error::InvalidFormat("chunk offset"))
+        );
+        assert_eq!(
+            other.load_chunk(201, &chunks[1].1).err(),
+            Some(Error::InvalidFormat("chunk length"))
+        );
+        assert_eq!(
+            RedisBloomFilter::from_header(&chunks[0].1[..40]).err(),
+            Some(Error::InvalidFormat("header length"))
+        );
+    }
+}
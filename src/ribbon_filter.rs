@@ -0,0 +1,422 @@
+use crate::{
+    builder_fingerprint,
+    xor_filter::{first_hash, key_hashes, mix, MAX_ATTEMPTS},
+    Error,
+};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::{field::BitField, vec::BitVec};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The default number of coefficients of each key.
+pub const DEFAULT_BAND_WIDTH: u32 = 64;
+
+/// The constants mixed with the hash value of a key to derive its coefficients and its fingerprint.
+const COEFFICIENTS_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+const FINGERPRINT_SEED: u64 = 0x632b_e59b_d9b4_e019;
+
+/// The equations of a key: the first slot, the coefficients of the `w` slots which start with it and the
+/// fingerprint which is the xor of the slots with a non-zero coefficient.
+struct Row {
+    start: usize,
+    coefficients: u64,
+    fingerprint: u64,
+}
+
+/// Implements the standard [ribbon filter](https://arxiv.org/abs/2103.02515) of Dillinger and Walzer,
+/// a static filter built once from a complete set of keys. Each key is mapped to a band of `w` consecutive slots
+/// and to random coefficients for them, and the filter stores the solution of the linear system over GF(2) which
+/// makes the xor of the slots with a non-zero coefficient equal to the fingerprint of the key.
+///
+/// The system is solved with Gaussian elimination as the keys are added, which fails when the equations of a key
+/// depend on the previous ones. The construction then retries with a new seed derived from the hasher builder.
+/// With fingerprints of `r` bits, the false-positive probability is `2^-r` and the filter uses slightly more than
+/// `r` bits per key, the overhead shrinking as the band width grows. Bands narrower than 32 coefficients
+/// only suit small sets of keys, since the elimination fails more often as the number of keys grows.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::RibbonFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let filter = RibbonFilter::<u32, _>::new(0..10_000u32, 8, 64, builder).unwrap();
+///
+/// assert!((0..10_000u32).all(|i| filter.contains(&i)));
+/// assert!(filter.bits_per_key() < 10.0);
+///```
+pub struct RibbonFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    seed: u64,
+    solution: BitVec,
+    slots: usize,
+    fingerprint_bits: u32,
+    band_width: u32,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> RibbonFilter<T, B>
+where
+    T: ?Sized,
+{
+    /// Returns the number of distinct keys in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the filter was built from an empty set of keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots of the filter.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Returns the number of bits of a fingerprint.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    /// Returns the number of coefficients of each key.
+    pub fn band_width(&self) -> u32 {
+        self.band_width
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn bit_len(&self) -> usize {
+        self.solution.len()
+    }
+
+    /// Returns the number of bits of the filter for each key.
+    pub fn bits_per_key(&self) -> f64 {
+        self.bit_len() as f64 / self.len.max(1) as f64
+    }
+
+    /// Returns the false-positive probability `2^-r`.
+    pub fn false_positive_rate(&self) -> f64 {
+        0.5f64.powi(self.fingerprint_bits as i32)
+    }
+
+    /// Computes the number of slots for a given number of keys. The elimination succeeds with high probability
+    /// when the ratio of spare slots is about `ln(n) / w`, so narrow bands need more spare slots.
+    fn slots_for(keys: usize, band_width: u32) -> usize {
+        let overhead = 1.0 + 0.8 * (keys.max(2) as f64).ln() / band_width as f64;
+        (keys as f64 * overhead).ceil() as usize + band_width as usize
+    }
+
+    fn row(hash: u64, slots: usize, fingerprint_bits: u32, band_width: u32) -> Row {
+        let starts = slots - band_width as usize + 1;
+        let start = ((hash as u128 * starts as u128) >> 64) as usize;
+
+        // The first coefficient is always one, so the row can be used as the pivot of its first slot.
+        let coefficients = (mix(hash, COEFFICIENTS_SEED) >> (u64::BITS - band_width)) | 1;
+        let fingerprint = mix(hash, FINGERPRINT_SEED) >> (u64::BITS - fingerprint_bits);
+
+        Row {
+            start,
+            coefficients,
+            fingerprint,
+        }
+    }
+
+    fn slot(&self, index: usize) -> u64 {
+        let bits = self.fingerprint_bits as usize;
+        self.solution[index * bits..(index + 1) * bits].load_le()
+    }
+
+    /// Solves the system for the hash values of the keys, mixed with a given seed.
+    fn solve(
+        hashes: &[u64],
+        seed: u64,
+        slots: usize,
+        fingerprint_bits: u32,
+        band_width: u32,
+    ) -> Option<BitVec> {
+        let mut coefficients = vec![0u64; slots];
+        let mut fingerprints = vec![0u64; slots];
+
+        for hash in hashes {
+            let mut row = Self::row(mix(*hash, seed), slots, fingerprint_bits, band_width);
+
+            loop {
+                if row.coefficients == 0 {
+                    // The equations of the key are a combination of the previous ones, which only succeeds
+                    // when the fingerprints agree.
+                    if row.fingerprint == 0 {
+                        break;
+                    }
+
+                    return None;
+                }
+
+                let shift = row.coefficients.trailing_zeros();
+                row.start += shift as usize;
+                row.coefficients >>= shift;
+
+                if coefficients[row.start] == 0 {
+                    coefficients[row.start] = row.coefficients;
+                    fingerprints[row.start] = row.fingerprint;
+                    break;
+                }
+
+                row.coefficients ^= coefficients[row.start];
+                row.fingerprint ^= fingerprints[row.start];
+            }
+        }
+
+        // Back substitution, from the last slot to the first one.
+        let bits = fingerprint_bits as usize;
+        let mut solution = BitVec::repeat(false, slots * bits);
+        let mut values = vec![0u64; slots];
+
+        for index in (0..slots).rev() {
+            let mut value = fingerprints[index];
+            let mut rest = coefficients[index] >> 1;
+
+            while rest != 0 {
+                let offset = rest.trailing_zeros() as usize + 1;
+                value ^= values[index + offset];
+                rest &= rest - 1;
+            }
+
+            values[index] = value;
+            solution[index * bits..(index + 1) * bits].store_le(value);
+        }
+
+        Some(solution)
+    }
+}
+
+impl<T, B> RibbonFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Builds a new [`RibbonFilter`] instance from a set of keys, with fingerprints of `fingerprint_bits` bits
+    /// (up to 32) and bands of `band_width` coefficients (up to 64). Duplicated keys are ignored.
+    /// It returns [`Error::ConstructionFailed`] when no seed derived from the hasher builder leads to a solution.
+    pub fn new<I>(
+        keys: I,
+        fingerprint_bits: u32,
+        band_width: u32,
+        builder: B,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        if fingerprint_bits == 0 || fingerprint_bits > u32::BITS {
+            return Err(Error::InvalidFingerprintBits(fingerprint_bits));
+        }
+
+        if band_width == 0 || band_width > u64::BITS {
+            return Err(Error::InvalidBandWidth(band_width));
+        }
+
+        let hashes = key_hashes(keys, &builder);
+        let slots = Self::slots_for(hashes.len(), band_width);
+        let mut rng = StdRng::seed_from_u64(builder_fingerprint(&builder));
+
+        for _ in 0..MAX_ATTEMPTS {
+            let seed = rng.gen();
+
+            if let Some(solution) = Self::solve(&hashes, seed, slots, fingerprint_bits, band_width)
+            {
+                return Ok(Self {
+                    builder,
+                    seed,
+                    solution,
+                    slots,
+                    fingerprint_bits,
+                    band_width,
+                    len: hashes.len(),
+                    _marker: PhantomData,
+                });
+            }
+        }
+
+        Err(Error::ConstructionFailed)
+    }
+
+    /// Builds a new [`RibbonFilter`] instance from a set of keys, with bands of [`DEFAULT_BAND_WIDTH`]
+    /// coefficients and fingerprints long enough to keep the false-positive probability under `fpr`.
+    pub fn with_fpr<I>(keys: I, fpr: f64, builder: B) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        let bits = (-fpr.log2()).ceil().max(1.0) as u32;
+        Self::new(keys, bits, DEFAULT_BAND_WIDTH, builder)
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let hash = mix(first_hash(item, &self.builder), self.seed);
+        let row = Self::row(hash, self.slots, self.fingerprint_bits, self.band_width);
+
+        let mut value = 0;
+        let mut rest = row.coefficients;
+        while rest != 0 {
+            value ^= self.slot(row.start + rest.trailing_zeros() as usize);
+            rest &= rest - 1;
+        }
+
+        value == row.fingerprint
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`RibbonFilter`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        seed: u64,
+        slots: usize,
+        fingerprint_bits: u32,
+        band_width: u32,
+        len: usize,
+        solution: Bytes,
+    }
+
+    impl<T, B> Serialize for RibbonFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                seed: self.seed,
+                slots: self.slots,
+                fingerprint_bits: self.fingerprint_bits,
+                band_width: self.band_width,
+                len: self.len,
+                solution: Bytes::from_bits(&self.solution),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for RibbonFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> RibbonFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            if repr.fingerprint_bits == 0 || repr.fingerprint_bits > u32::BITS {
+                return Err(Error::InvalidFingerprintBits(repr.fingerprint_bits));
+            }
+
+            if repr.band_width == 0 || repr.band_width > u64::BITS {
+                return Err(Error::InvalidBandWidth(repr.band_width));
+            }
+
+            if repr.slots < repr.band_width as usize {
+                return Err(Error::InvalidFormat("number of slots"));
+            }
+
+            let solution = repr
+                .solution
+                .into_bits(repr.slots.saturating_mul(repr.fingerprint_bits as usize))?;
+
+            Ok(Self {
+                builder,
+                seed: repr.seed,
+                solution,
+                slots: repr.slots,
+                fingerprint_bits: repr.fingerprint_bits,
+                band_width: repr.band_width,
+                len: repr.len,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn no_false_negatives() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = RibbonFilter::<u32, _>::with_fpr(0..10_000u32, 0.01, builder).unwrap();
+
+        assert_eq!(filter.fingerprint_bits(), 7);
+        assert!((0..10_000u32).all(|i| filter.contains(&i)));
+
+        let false_positives = (10_000..60_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 500);
+    }
+
+    #[test]
+    fn band_widths() {
+        for band_width in [16, 32, 64] {
+            let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+            let filter = RibbonFilter::<u32, _>::new(0..1000u32, 12, band_width, builder).unwrap();
+
+            assert!((0..1000u32).all(|i| filter.contains(&i)));
+        }
+    }
+
+    #[test]
+    fn invalid_band_width() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = RibbonFilter::<u32, _>::new(0..1000u32, 8, 65, builder);
+        assert_eq!(res.err(), Some(Error::InvalidBandWidth(65)));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{xxph3::xxph3_64, Error};
+use std::io::Read;
+
+/// The number of bytes of metadata at the end of a full filter block.
+const METADATA_LEN: usize = 5;
+
+/// The seed of the 32 bits hash of the legacy filters.
+const LEGACY_SEED: u32 = 0xbc9f_1d34;
+
+/// The multiplier of the 32 bits hash of the legacy filters.
+const LEGACY_M: u32 = 0xc6a4_a793;
+
+/// The multiplier which derives the bit of each probe of a FastLocalBloom filter from the previous one.
+const GOLDEN_RATIO: u32 = 0x9e37_79b9;
+
+/// Computes the 32 bits hash of some bytes used by the legacy filters, a variant of MurmurHash1. The bytes of
+/// the tail are sign-extended, like the C++ code does.
+fn legacy_hash(bytes: &[u8]) -> u32 {
+    let mut h = LEGACY_SEED ^ (bytes.len() as u32).wrapping_mul(LEGACY_M);
+
+    let mut words = bytes.chunks_exact(4);
+    for word in &mut words {
+        h = h.wrapping_add(u32::from_le_bytes(word.try_into().unwrap()));
+        h = h.wrapping_mul(LEGACY_M);
+        h ^= h >> 16;
+    }
+
+    let tail = words.remainder();
+    if !tail.is_empty() {
+        h = tail.iter().enumerate().fold(h, |h, (i, byte)| {
+            h.wrapping_add((*byte as i8 as u32) << (8 * i))
+        });
+        h = h.wrapping_mul(LEGACY_M);
+        h ^= h >> 24;
+    }
+
+    h
+}
+
+/// How the bits of a filter block are laid out, read from its metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// An empty filter, which matches no key.
+    Empty,
+    /// A filter without probes or with a layout reserved for future use, which RocksDB reads as a filter
+    /// matching every key.
+    Reserved,
+    /// The cache local bloom filter written since RocksDB 6.10, with 64 bytes cache lines.
+    FastLocal { probes: u32 },
+    /// The full filter written before, where each key sets the bits of a line of `1 << log2_line_bytes` bytes.
+    Legacy {
+        probes: u32,
+        lines: u32,
+        log2_line_bytes: u32,
+    },
+}
+
+/// Reads the full filter blocks written by the bloom filter policy of [RocksDB](https://github.com/facebook/rocksdb),
+/// both the FastLocalBloom filters and the legacy ones, so the keys of a table can be tested against its filter
+/// without opening the database. The filter is read-only: it answers like RocksDB does, including for the
+/// reserved layouts, which match every key.
+///
+/// The bytes are the contents of a filter block, or of one partition of a partitioned filter, without the block
+/// trailer. The keys are the user keys, or their prefixes when the table was built with a prefix extractor.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::RocksDbBloomFilter;
+///
+/// // An empty block is written for a table without keys.
+/// let filter = RocksDbBloomFilter::from_bytes(&[]).unwrap();
+/// assert!(!filter.contains("Hello world!"));
+///
+/// // A FastLocalBloom filter of 64 bytes, with 6 probes and all the bits set.
+/// let mut block = vec![0xff; 64];
+/// block.extend_from_slice(&[0xff, 0, 6, 0, 0]);
+///
+/// let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+/// assert_eq!(filter.bit_len(), 512);
+/// assert!(filter.contains("Hello world!"));
+///```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RocksDbBloomFilter {
+    block: Vec<u8>,
+    layout: Layout,
+}
+
+impl RocksDbBloomFilter {
+    /// Reads a filter from the contents of a filter block. It fails only for the Ribbon filters, which are
+    /// written in the same blocks by the Ribbon filter policy; the other invalid blocks are read like RocksDB
+    /// reads them, as filters which match every key.
+    pub fn from_bytes(block: &[u8]) -> Result<Self, Error> {
+        let layout = Self::layout(block)?;
+
+        Ok(Self {
+            block: block.to_vec(),
+            layout,
+        })
+    }
+
+    /// Reads a filter from all the bytes of a reader, see [`RocksDbBloomFilter::from_bytes`].
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut block = Vec::new();
+        reader.read_to_end(&mut block)?;
+
+        Self::from_bytes(&block)
+    }
+
+    fn layout(block: &[u8]) -> Result<Layout, Error> {
+        if block.len() <= METADATA_LEN {
+            return Ok(Layout::Empty);
+        }
+
+        let len = block.len() - METADATA_LEN;
+        let metadata = &block[len..];
+
+        match metadata[0] as i8 {
+            -1 => {
+                let probes = (metadata[2] & 31) as u32;
+                let log2_block_bytes = ((metadata[2] >> 5) & 7) + 6;
+                let rest = u16::from_le_bytes([metadata[3], metadata[4]]);
+
+                // Only the FastLocalBloom filters with 64 bytes cache lines are written for now,
+                // and their bits are made of whole cache lines.
+                if !(1..=30).contains(&probes)
+                    || !len.is_multiple_of(64)
+                    || rest != 0
+                    || metadata[1] != 0
+                    || log2_block_bytes != 6
+                {
+                    return Ok(Layout::Reserved);
+                }
+
+                Ok(Layout::FastLocal { probes })
+            }
+            -2 => Err(Error::InvalidFormat("filter implementation")),
+            probes if probes < 1 => Ok(Layout::Reserved),
+            probes => {
+                let lines = u32::from_le_bytes(metadata[1..].try_into().unwrap());
+
+                // The lines have 64 bytes on most platforms, and a power of two bytes on the others.
+                let log2_line_bytes = if lines as u64 * 64 == len as u64 {
+                    6
+                } else if lines == 0 || !len.is_multiple_of(lines as usize) {
+                    return Ok(Layout::Reserved);
+                } else {
+                    let line_bytes = len / lines as usize;
+                    if !line_bytes.is_power_of_two() {
+                        return Ok(Layout::Reserved);
+                    }
+
+                    line_bytes.trailing_zeros()
+                };
+
+                Ok(Layout::Legacy {
+                    probes: probes as u32,
+                    lines,
+                    log2_line_bytes,
+                })
+            }
+        }
+    }
+
+    /// Returns the number of bits in the filter, without the metadata.
+    pub fn bit_len(&self) -> usize {
+        self.block.len().saturating_sub(METADATA_LEN) * 8
+    }
+
+    /// Returns the number of bits tested for each key, or zero when the filter matches either no key or
+    /// every key.
+    pub fn probes(&self) -> usize {
+        match self.layout {
+            Layout::FastLocal { probes } | Layout::Legacy { probes, .. } => probes as usize,
+            Layout::Empty | Layout::Reserved => 0,
+        }
+    }
+
+    /// Returns the contents of the filter block the filter was read from.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.block
+    }
+
+    /// Checks if a given key may be present in the table, like `KeyMayMatch` does.
+    pub fn contains<K: AsRef<[u8]> + ?Sized>(&self, key: &K) -> bool {
+        let key = key.as_ref();
+        let data = &self.block[..self.block.len().saturating_sub(METADATA_LEN)];
+        let is_set = |line: &[u8], bit: u32| line[(bit >> 3) as usize] & (1 << (bit & 7)) != 0;
+
+        match self.layout {
+            Layout::Empty => false,
+            Layout::Reserved => true,
+            Layout::FastLocal { probes } => {
+                let hash = xxph3_64(key);
+
+                // The lower half of the hash value selects the line and the upper half the bits in the line.
+                let lines = (data.len() >> 6) as u64;
+                let offset = (((hash & 0xffff_ffff) * lines) >> 32) << 6;
+                let line = &data[offset as usize..];
+
+                let mut h = (hash >> 32) as u32;
+                (0..probes).all(|_| {
+                    let bit = h >> 23;
+                    h = h.wrapping_mul(GOLDEN_RATIO);
+                    is_set(line, bit)
+                })
+            }
+            Layout::Legacy {
+                probes,
+                lines,
+                log2_line_bytes,
+            } => {
+                let mut h = legacy_hash(key);
+
+                let offset = ((h % lines) as usize) << log2_line_bytes;
+                let line = &data[offset..];
+                let mask = (1 << (log2_line_bytes + 3)) - 1;
+
+                let delta = h.rotate_right(17);
+                (0..probes).all(|_| {
+                    let bit = h & mask;
+                    h = h.wrapping_add(delta);
+                    is_set(line, bit)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`RocksDbBloomFilter`], the contents of its filter block.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        block: Bytes,
+    }
+
+    impl Serialize for RocksDbBloomFilter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                block: Bytes(self.block.clone()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RocksDbBloomFilter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_bytes(&repr.block.0).map_err(de_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bits set by RocksDB in a FastLocalBloom filter of 128 bytes, with 6 probes, for the keys `key0`
+    /// to `key9`.
+    const FAST_LOCAL_BITS: [usize; 57] = [
+        1, 29, 34, 117, 229, 266, 312, 352, 365, 372, 399, 405, 418, 427, 435, 436, 467, 500, 515,
+        518, 528, 540, 547, 555, 584, 589, 601, 611, 627, 633, 667, 672, 675, 693, 714, 720, 727,
+        732, 754, 762, 766, 770, 775, 779, 802, 813, 815, 878, 883, 908, 922, 929, 952, 991, 1000,
+        1003, 1018,
+    ];
+
+    /// The bits set by RocksDB in a legacy filter of two lines of 64 bytes, with 6 probes, for the same keys.
+    const LEGACY_BITS: [usize; 17] = [
+        25, 50, 75, 100, 125, 150, 153, 203, 253, 281, 306, 331, 381, 406, 409, 459, 509,
+    ];
+
+    fn new_block(len: usize, bits: &[usize], metadata: [u8; METADATA_LEN]) -> Vec<u8> {
+        let mut block = vec![0u8; len];
+        bits.iter().for_each(|bit| block[bit / 8] |= 1 << (bit % 8));
+        block.extend_from_slice(&metadata);
+        block
+    }
+
+    #[test]
+    fn legacy_hash_vectors() {
+        assert_eq!(legacy_hash(b"abc"), 0x855d_012f);
+        assert_eq!(legacy_hash(b"abcdefgh"), 0xb2ce_35dd);
+        assert_eq!(legacy_hash(&[0xff, 0x80, 0x7f]), 0x7279_1516);
+    }
+
+    #[test]
+    fn golden_fast_local() {
+        let block = new_block(128, &FAST_LOCAL_BITS, [0xff, 0, 6, 0, 0]);
+        let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+        assert_eq!(filter.bit_len(), 1024);
+        assert_eq!(filter.probes(), 6);
+
+        assert!((0..10).all(|i| filter.contains(&format!("key{i}"))));
+        assert!((10..1000).all(|i| !filter.contains(&format!("key{i}"))));
+    }
+
+    #[test]
+    fn golden_legacy() {
+        let block = new_block(128, &LEGACY_BITS, [6, 2, 0, 0, 0]);
+        let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+        assert_eq!(filter.probes(), 6);
+
+        assert!((0..10).all(|i| filter.contains(&format!("key{i}"))));
+        assert!((10..1000).all(|i| !filter.contains(&format!("key{i}"))));
+
+        // A single line of 128 bytes.
+        let block = new_block(128, &[], [6, 1, 0, 0, 0]);
+        let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+        assert_eq!(filter.probes(), 6);
+        assert!(!filter.contains("key0"));
+    }
+
+    #[test]
+    fn reserved_layouts() {
+        let matches_all = |metadata| {
+            let filter = RocksDbBloomFilter::from_bytes(&new_block(64, &[], metadata)).unwrap();
+            filter.probes() == 0 && filter.contains("key0")
+        };
+
+        // No probes, an unknown marker, 31 probes, 128 bytes lines, a seed and lines which do not split the bits.
+        assert!(matches_all([0, 0, 0, 0, 0]));
+        assert!(matches_all([0xfd, 0, 6, 0, 0]));
+        assert!(matches_all([0xff, 0, 31, 0, 0]));
+        assert!(matches_all([0xff, 0, 0x26, 0, 0]));
+        assert!(matches_all([0xff, 0, 6, 1, 0]));
+        assert!(matches_all([6, 3, 0, 0, 0]));
+        assert!(matches_all([6, 0, 0, 0, 0]));
+
+        let filter = RocksDbBloomFilter::from_bytes(&[6, 1, 0, 0, 0]).unwrap();
+        assert_eq!(filter.bit_len(), 0);
+        assert!(!filter.contains("key0"));
+
+        assert_eq!(
+            RocksDbBloomFilter::from_bytes(&new_block(64, &[], [0xfe, 0, 0, 0, 0])),
+            Err(Error::InvalidFormat("filter implementation"))
+        );
+    }
+
+    #[test]
+    fn partial_cache_lines() {
+        // The bits of a FastLocalBloom filter which do not fill whole cache lines match all the keys.
+        [10, 63, 96].into_iter().for_each(|len| {
+            let mut block = vec![0xff; len];
+            block.extend_from_slice(&[0xff, 0, 6, 0, 0]);
+
+            let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+            assert_eq!(filter.probes(), 0);
+            assert!((0..100).all(|i| filter.contains(&format!("key{i}"))));
+        });
+    }
+}
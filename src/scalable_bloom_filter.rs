@@ -0,0 +1,391 @@
+use crate::{bit_index, params, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::vec::BitVec;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// The default growth factor of the capacity of the sub-filters.
+pub const DEFAULT_GROWTH: usize = 2;
+
+/// The default ratio by which the false-positive probability of each sub-filter is tightened.
+pub const DEFAULT_TIGHTENING: f64 = 0.85;
+
+/// One of the sub-filters of a [`ScalableBloomFilter`].
+struct Slice {
+    bits: BitVec,
+    hashes: usize,
+    capacity: usize,
+    len: usize,
+    fpr: f64,
+}
+
+impl Slice {
+    fn new(capacity: usize, fpr: f64) -> Self {
+        let bits = params::optimal_bits(capacity, fpr);
+        let hashes = params::optimal_hashes_for_fpr(fpr);
+
+        Self {
+            bits: BitVec::repeat(false, bits),
+            hashes,
+            capacity,
+            len: 0,
+            fpr,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+}
+
+/// Implements the [scalable bloom filter](https://doi.org/10.1016/j.ipl.2006.10.007) of Almeida et al.
+/// The filter starts with a sub-filter sized for an initial capacity. When it is full, a new sub-filter is added,
+/// with a capacity `growth` times larger and a false-positive probability `tightening` times smaller,
+/// so the compound false-positive probability stays under the target one however many items are inserted.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::ScalableBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = ScalableBloomFilter::<u32, _>::new(100, 0.01, builder).unwrap();
+///
+/// (0..1000u32).for_each(|i| filter.insert(&i));
+///
+/// assert!((0..1000u32).all(|i| filter.contains(&i)));
+/// assert!(filter.slices() > 1);
+/// assert!(filter.fpr_bound() < 0.01);
+///```
+pub struct ScalableBloomFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    slices: Vec<Slice>,
+    growth: usize,
+    tightening: f64,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> ScalableBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+{
+    /// Creates a new [`ScalableBloomFilter`] instance with a given initial capacity and a target
+    /// false-positive probability, using the [`DEFAULT_GROWTH`] and [`DEFAULT_TIGHTENING`] factors.
+    pub fn new(initial_capacity: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        Self::with_growth(
+            initial_capacity,
+            fpr,
+            DEFAULT_GROWTH,
+            DEFAULT_TIGHTENING,
+            builder,
+        )
+    }
+
+    /// Creates a new [`ScalableBloomFilter`] instance with a given initial capacity, target false-positive
+    /// probability, growth factor of the capacity and tightening ratio of the false-positive probability.
+    pub fn with_growth(
+        initial_capacity: usize,
+        fpr: f64,
+        growth: usize,
+        tightening: f64,
+        builder: B,
+    ) -> Result<Self, Error> {
+        if initial_capacity == 0 {
+            return Err(Error::ZeroSize("initial capacity"));
+        }
+
+        if growth == 0 {
+            return Err(Error::ZeroSize("growth factor"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        if !(tightening > 0.0 && tightening < 1.0) {
+            return Err(Error::InvalidProbability(tightening));
+        }
+
+        // The false-positive probabilities of the slices form a geometric series which converges to `fpr`.
+        let first = Slice::new(initial_capacity, fpr * (1.0 - tightening));
+
+        Ok(Self {
+            builder,
+            slices: vec![first],
+            growth,
+            tightening,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the number of distinct items inserted in the filter.
+    pub fn len(&self) -> usize {
+        self.slices.iter().map(|slice| slice.len).sum()
+    }
+
+    /// Returns true when no item was inserted in the filter.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of sub-filters.
+    pub fn slices(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// Returns the total number of bits of the sub-filters.
+    pub fn bit_len(&self) -> usize {
+        self.slices.iter().map(|slice| slice.bits.len()).sum()
+    }
+
+    /// Returns the upper bound of the compound false-positive probability of the current sub-filters.
+    pub fn fpr_bound(&self) -> f64 {
+        1.0 - self
+            .slices
+            .iter()
+            .map(|slice| 1.0 - slice.fpr)
+            .product::<f64>()
+    }
+
+    fn grow(&mut self) {
+        let last = self
+            .slices
+            .last()
+            .expect("the filter has at least one slice");
+        let slice = Slice::new(
+            last.capacity.saturating_mul(self.growth),
+            last.fpr * self.tightening,
+        );
+
+        self.slices.push(slice);
+    }
+}
+
+impl<T, B> ScalableBloomFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item. An item which is already present is not inserted again,
+    /// so it does not count towards the capacity of the current sub-filter.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        if self.contains(item) {
+            return;
+        }
+
+        if self.slices.last().is_some_and(Slice::is_full) {
+            self.grow();
+        }
+
+        let slice = self
+            .slices
+            .last_mut()
+            .expect("the filter has at least one slice");
+        let len = slice.bits.len();
+
+        self.builder
+            .hashes_one(item)
+            .take(slice.hashes)
+            .for_each(|hash| slice.bits.set(bit_index(hash, len), true));
+
+        slice.len += 1;
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.slices.iter().rev().any(|slice| {
+            let len = slice.bits.len();
+
+            self.builder
+                .hashes_one(item)
+                .take(slice.hashes)
+                .all(|hash| slice.bits[bit_index(hash, len)])
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`ScalableBloomFilter`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        growth: usize,
+        tightening: f64,
+        slices: Vec<SliceRepr>,
+    }
+
+    /// The serialized form of a sub-filter of a [`ScalableBloomFilter`].
+    #[derive(Serialize, Deserialize)]
+    struct SliceRepr {
+        bit_len: usize,
+        hashes: usize,
+        capacity: usize,
+        len: usize,
+        fpr: f64,
+        bits: Bytes,
+    }
+
+    impl<T, B> Serialize for ScalableBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let slices = self.slices.iter().map(|slice| SliceRepr {
+                bit_len: slice.bits.len(),
+                hashes: slice.hashes,
+                capacity: slice.capacity,
+                len: slice.len,
+                fpr: slice.fpr,
+                bits: Bytes::from_bits(&slice.bits),
+            });
+
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                growth: self.growth,
+                tightening: self.tightening,
+                slices: slices.collect(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for ScalableBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> ScalableBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            if repr.growth == 0 {
+                return Err(Error::ZeroSize("growth factor"));
+            }
+
+            if !(repr.tightening > 0.0 && repr.tightening < 1.0) {
+                return Err(Error::InvalidProbability(repr.tightening));
+            }
+
+            if repr.slices.is_empty() {
+                return Err(Error::InvalidFormat("no sub-filters"));
+            }
+
+            let slices = repr
+                .slices
+                .into_iter()
+                .map(|slice| {
+                    if slice.bit_len == 0 || slice.hashes == 0 {
+                        return Err(Error::InvalidFormat("empty sub-filter"));
+                    }
+
+                    if slice.capacity == 0 {
+                        return Err(Error::InvalidFormat("sub-filter capacity"));
+                    }
+
+                    if !(slice.fpr > 0.0 && slice.fpr < 1.0) {
+                        return Err(Error::InvalidFormat("sub-filter probability"));
+                    }
+
+                    Ok(Slice {
+                        bits: slice.bits.into_bits(slice.bit_len)?,
+                        hashes: slice.hashes,
+                        capacity: slice.capacity,
+                        len: slice.len,
+                        fpr: slice.fpr,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Self {
+                builder,
+                slices,
+                growth: repr.growth,
+                tightening: repr.tightening,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn grows() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = ScalableBloomFilter::<u32, _>::new(10, 0.01, builder).unwrap();
+
+        (0..10u32).for_each(|i| filter.insert(&i));
+        assert_eq!(filter.slices(), 1);
+
+        (0..10u32).for_each(|i| filter.insert(&i));
+        assert_eq!(filter.len(), 10);
+        assert_eq!(filter.slices(), 1);
+
+        filter.insert(&10);
+        assert_eq!(filter.slices(), 2);
+        assert!((0..=10u32).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn bounded_fpr() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = ScalableBloomFilter::<u32, _>::new(100, 0.01, builder).unwrap();
+
+        (0..10_000u32).for_each(|i| filter.insert(&i));
+        assert!(filter.fpr_bound() < 0.01);
+
+        let false_positives = (10_000..20_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 200);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{builder_fingerprint, Counters, Error};
+use aabel_multihash_rs::HasherExt;
+use bitvec::{field::BitField, slice::BitSlice, store::BitStore, vec::BitVec};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, hash::BuildHasher};
+
+/// The alphabet of the standard base64 encoding.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The packed content of a filter. The binary formats store the raw bytes, and the human-readable
+/// formats store them as a base64 string, so a bit array never becomes a list of booleans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Bytes(pub(crate) Vec<u8>);
+
+impl Bytes {
+    /// Packs the bits, eight in each byte starting with the least significant one.
+    pub(crate) fn from_bits<S: BitStore>(bits: &BitSlice<S>) -> Self {
+        Self(bits.chunks(8).map(|byte| byte.load_le::<u8>()).collect())
+    }
+
+    /// Unpacks `len` bits packed by [`Bytes::from_bits`].
+    pub(crate) fn into_bits<S: BitStore>(self, len: usize) -> Result<BitVec<S>, Error> {
+        if self.0.len() != len.div_ceil(8) {
+            return Err(Error::InvalidFormat("bit array length"));
+        }
+
+        let mut bits = BitVec::repeat(false, len);
+        bits.chunks_mut(8)
+            .zip(self.0)
+            .for_each(|(chunk, byte)| chunk.store_le(byte));
+
+        Ok(bits)
+    }
+
+    /// Packs values of `width` bits each.
+    pub(crate) fn from_values<I>(values: I, width: u32) -> Self
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let width = width as usize;
+        let mut bits: BitVec = BitVec::new();
+
+        values.into_iter().for_each(|value| {
+            let start = bits.len();
+            bits.resize(start + width, false);
+            bits[start..].store_le(value);
+        });
+
+        Self::from_bits(&bits)
+    }
+
+    /// Unpacks `len` values of `width` bits packed by [`Bytes::from_values`].
+    pub(crate) fn into_values(self, len: usize, width: u32) -> Result<Vec<u64>, Error> {
+        let width = width as usize;
+        let bits: BitVec = self.into_bits(len.saturating_mul(width))?;
+
+        Ok(bits.chunks(width).map(|chunk| chunk.load_le()).collect())
+    }
+
+    /// Packs an array of counters, with as many bits for each counter as [`Counters::MAX`] needs.
+    pub(crate) fn from_counters<C: Counters>(counters: &C) -> Self {
+        let values = (0..counters.len()).map(|index| counters.get(index) as u64);
+        Self::from_values(values, counter_bits::<C>())
+    }
+
+    /// Unpacks `len` counters packed by [`Bytes::from_counters`].
+    pub(crate) fn into_counters<C: Counters>(self, len: usize) -> Result<C, Error> {
+        let values = self.into_values(len, counter_bits::<C>())?;
+        let mut counters = C::with_len(len);
+
+        values
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, value)| counters.set(index, value as u16));
+
+        Ok(counters)
+    }
+}
+
+/// Returns the number of bits of a counter of an array of counters.
+pub(crate) fn counter_bits<C: Counters>() -> u32 {
+    u16::BITS - C::MAX.leading_zeros()
+}
+
+/// Checks that a filter was serialized with a hasher builder with the same fingerprint as `builder`.
+pub(crate) fn check_hasher<B>(hasher: u64, builder: &B) -> Result<(), Error>
+where
+    B: BuildHasher,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    if hasher != builder_fingerprint(builder) {
+        return Err(Error::IncompatibleHashers);
+    }
+
+    Ok(())
+}
+
+/// Checks that a serialized filter has the sizes expected by the type it is deserialized into.
+pub(crate) fn check_size<T: PartialEq>(actual: T, expected: T) -> Result<(), Error> {
+    if actual != expected {
+        return Err(Error::IncompatibleSizes);
+    }
+
+    Ok(())
+}
+
+/// Converts the error of rebuilding a filter into the error of the deserializer.
+pub(crate) fn de_error<E: de::Error>(err: Error) -> E {
+    E::custom(err)
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode_base64(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array or a base64 string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Bytes, E> {
+                decode_base64(value)
+                    .map(Bytes)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Bytes, E> {
+                Ok(Bytes(value.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Bytes, E> {
+                Ok(Bytes(value))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+
+                Ok(Bytes(bytes))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    bytes.chunks(3).for_each(|chunk| {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, byte)| {
+            word | (*byte as u32) << (16 - 8 * i)
+        });
+
+        (0..4).for_each(|i| {
+            if i <= chunk.len() {
+                text.push(BASE64[(word >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        });
+    });
+
+    text
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+
+    for chunk in text.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+
+        let mut word = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64.iter().position(|x| x == c)? as u32;
+            word |= value << (18 - 6 * i);
+        }
+
+        (0..3 - padding).for_each(|i| bytes.push((word >> (16 - 8 * i)) as u8));
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Counters4;
+
+    #[test]
+    fn base64() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode_base64(bytes), text);
+            assert_eq!(decode_base64(text).unwrap(), bytes);
+        }
+
+        assert_eq!(decode_base64("Zm9"), None);
+        assert_eq!(decode_base64("Zm9*"), None);
+    }
+
+    #[test]
+    fn pack_unpack() {
+        let mut bits: BitVec = BitVec::repeat(false, 13);
+        bits.set(0, true);
+        bits.set(12, true);
+
+        let bytes = Bytes::from_bits(&bits);
+        assert_eq!(bytes.0, vec![0x01, 0x10]);
+        assert_eq!(bytes.clone().into_bits::<usize>(13).unwrap(), bits);
+        assert_eq!(
+            bytes.into_bits::<usize>(17).err(),
+            Some(Error::InvalidFormat("bit array length"))
+        );
+
+        let mut counters = Counters4::with_len(3);
+        counters.set(0, 3);
+        counters.set(2, 15);
+
+        let bytes = Bytes::from_counters(&counters);
+        assert_eq!(bytes.0, vec![0x03, 0x0f]);
+        assert_eq!(bytes.into_counters::<Counters4>(3).unwrap(), counters);
+    }
+}
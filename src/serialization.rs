@@ -0,0 +1,392 @@
+use crate::{builder_fingerprint, BloomFilter, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use bitvec::{field::BitField, slice::BitSlice};
+use std::{
+    hash::BuildHasher,
+    io::{Read, Write},
+};
+
+/// The first bytes of a serialized filter.
+const MAGIC: [u8; 4] = *b"ABLF";
+
+/// The version of the serialization format.
+const VERSION: u16 = 1;
+
+/// The identifier of the scheme mapping the hash values of an item to the bits of a filter.
+const SCHEME_MOD_LEN: u8 = 1;
+
+/// The length of the header: magic, version, kind, scheme, bit length, hashes and hasher fingerprint.
+const HEADER_LEN: usize = 4 + 2 + 1 + 1 + 8 + 4 + 8;
+
+/// The length of the checksum which ends a serialized filter.
+const CHECKSUM_LEN: usize = 4;
+
+/// The kinds of serialized filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Kind {
+    BloomFilter = 1,
+}
+
+/// The header of a serialized filter. All the fields are written in little-endian order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) kind: Kind,
+    pub(crate) bit_len: u64,
+    pub(crate) hashes: u32,
+    pub(crate) hasher: u64,
+}
+
+impl Header {
+    pub(crate) fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[6] = self.kind as u8;
+        bytes[7] = SCHEME_MOD_LEN;
+        bytes[8..16].copy_from_slice(&self.bit_len.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.hashes.to_le_bytes());
+        bytes[20..28].copy_from_slice(&self.hasher.to_le_bytes());
+
+        bytes
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Self, Error> {
+        if bytes[0..4] != MAGIC {
+            return Err(Error::InvalidFormat("magic"));
+        }
+
+        if u16::from_le_bytes([bytes[4], bytes[5]]) != VERSION {
+            return Err(Error::InvalidFormat("version"));
+        }
+
+        let kind = match bytes[6] {
+            1 => Kind::BloomFilter,
+            _ => return Err(Error::InvalidFormat("filter kind")),
+        };
+
+        if bytes[7] != SCHEME_MOD_LEN {
+            return Err(Error::InvalidFormat("hashing scheme"));
+        }
+
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+        Ok(Self {
+            kind,
+            bit_len: u64_at(8),
+            hashes: u32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            hasher: u64_at(20),
+        })
+    }
+
+    /// Checks that the header describes a filter of a given kind, size and hasher builder.
+    pub(crate) fn check(&self, expected: &Self) -> Result<(), Error> {
+        if self.kind != expected.kind {
+            return Err(Error::InvalidFormat("filter kind"));
+        }
+
+        if self.bit_len != expected.bit_len || self.hashes != expected.hashes {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if self.hasher != expected.hasher {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes a header followed by the bits, as little-endian 64 bits words, and the checksum.
+pub(crate) fn write_bits(header: &Header, bits: &BitSlice<u64>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + bits.len().div_ceil(64) * 8 + CHECKSUM_LEN);
+
+    bytes.extend_from_slice(&header.to_bytes());
+    bits.chunks(64)
+        .for_each(|word| bytes.extend_from_slice(&word.load_le::<u64>().to_le_bytes()));
+
+    let checksum = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    bytes
+}
+
+/// Deserializes the bits of a filter described by the `expected` header.
+pub(crate) fn read_bits<R: Read>(
+    mut reader: R,
+    expected: &Header,
+    bits: &mut BitSlice<u64>,
+) -> Result<(), Error> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    Header::from_bytes(&header)?.check(expected)?;
+
+    let mut words = vec![0u8; bits.len().div_ceil(64) * 8];
+    reader.read_exact(&mut words)?;
+
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    reader.read_exact(&mut checksum)?;
+
+    if u32::from_le_bytes(checksum) != crc32_update(crc32(&header), &words) {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    bits.chunks_mut(64)
+        .zip(words.chunks_exact(8))
+        .for_each(|(word, bytes)| word.store_le(u64::from_le_bytes(bytes.try_into().unwrap())));
+
+    Ok(())
+}
+
+/// The lookup table of the CRC-32 (ISO-HDLC) checksum.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32 checksum of some bytes.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Extends the CRC-32 checksum of some bytes with the bytes which follow them.
+pub(crate) fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+impl<T, B, const K: usize, const H: usize> BloomFilter<T, B, K, H>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Serializes the filter. The bytes start with a header recording the format version, the filter kind,
+    /// the number of bits, the number of hash values for each item and the hashing scheme, followed by the bits
+    /// as little-endian 64 bits words and a CRC-32 checksum, so they do not depend on the platform.
+    ///
+    /// # Example
+    ///
+    ///```
+    /// use aabel_bloom_rs::BloomFilter;
+    /// use aabel_multihash_rs::BuildPairHasher;
+    ///
+    /// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+    /// let mut filter = BloomFilter::<str, _>::new(builder());
+    /// filter.insert("Hello world!");
+    ///
+    /// let bytes = filter.to_bytes();
+    /// let mut filter = BloomFilter::<str, _>::from_bytes(&bytes, builder()).unwrap();
+    /// assert!(filter.contains("Hello world!"));
+    ///```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        write_bits(&self.header(), &self.bits)
+    }
+
+    /// Deserializes a filter serialized by [`BloomFilter::to_bytes`]. It fails when the bytes are not a valid
+    /// serialized filter, when the filter has a different size or when it was built with a different hasher builder.
+    pub fn from_bytes(bytes: &[u8], builder: B) -> Result<Self, Error> {
+        let mut reader = bytes;
+        let filter = Self::read_from(&mut reader, builder)?;
+
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat("trailing bytes"));
+        }
+
+        Ok(filter)
+    }
+
+    /// Writes the serialized filter, see [`BloomFilter::to_bytes`].
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a filter written by [`BloomFilter::write_to`], see [`BloomFilter::from_bytes`].
+    pub fn read_from<R: Read>(reader: R, builder: B) -> Result<Self, Error> {
+        let mut filter = Self::new(builder);
+        read_bits(reader, &filter.header(), &mut filter.bits)?;
+        Ok(filter)
+    }
+
+    fn header(&self) -> Header {
+        Header {
+            kind: Kind::BloomFilter,
+            bit_len: self.bits.len() as u64,
+            hashes: H as u32,
+            hasher: builder_fingerprint(&self.builder),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{check_hasher, check_size, de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`BloomFilter`]. The number of bits and hash values reject
+    /// a filter deserialized into a [`BloomFilter`] with different `K` or `H` arguments.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        bit_len: u64,
+        hashes: u32,
+        bits: Bytes,
+    }
+
+    impl<T, B, const K: usize, const H: usize> Serialize for BloomFilter<T, B, K, H>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let header = self.header();
+
+            Repr {
+                hasher: header.hasher,
+                bit_len: header.bit_len,
+                hashes: header.hashes,
+                bits: Bytes::from_bits(&self.bits),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B, const K: usize, const H: usize> Deserialize<'de> for BloomFilter<T, B, K, H>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B, const K: usize, const H: usize> BloomFilter<T, B, K, H>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter has a different size or when it was built with a different hasher builder.
+        ///
+        /// # Example
+        ///
+        ///```
+        /// use aabel_bloom_rs::BloomFilter;
+        /// use aabel_multihash_rs::BuildPairHasher;
+        ///
+        /// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        /// let mut filter = BloomFilter::<str, _>::new(builder());
+        /// filter.insert("Hello world!");
+        ///
+        /// let json = serde_json::to_string(&filter).unwrap();
+        /// let mut de = serde_json::Deserializer::from_str(&json);
+        /// let mut filter = BloomFilter::<str, _>::deserialize_with(&mut de, builder()).unwrap();
+        /// assert!(filter.contains("Hello world!"));
+        ///```
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            let mut filter = Self::new(builder);
+            let expected = filter.header();
+
+            check_size(
+                (repr.bit_len, repr.hashes),
+                (expected.bit_len, expected.hashes),
+            )?;
+            check_hasher(repr.hasher, &filter.builder)?;
+
+            let bits = repr.bits.into_bits(filter.bits.len())?;
+            filter.bits.copy_from_bitslice(&bits);
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn crc32_check() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = BloomFilter::<u32, _>::new(builder());
+        (0..100u32).for_each(|i| filter.insert(&i));
+
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + 100 * 8 + CHECKSUM_LEN);
+        assert_eq!(bytes, filter.to_bytes());
+
+        let mut other = BloomFilter::<u32, _>::read_from(bytes.as_slice(), builder()).unwrap();
+        assert_eq!(other.bits, filter.bits);
+        assert!((0..100u32).all(|i| other.contains(&i)));
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = BloomFilter::<u32, _>::new(builder());
+        let bytes = filter.to_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN] ^= 1;
+        let res = BloomFilter::<u32, _>::from_bytes(&corrupted, builder());
+        assert_eq!(res.err(), Some(Error::ChecksumMismatch));
+
+        let res = BloomFilter::<u32, _>::from_bytes(&bytes[..bytes.len() - 1], builder());
+        assert_eq!(
+            res.err(),
+            Some(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+
+        let res = BloomFilter::<u32, _, 100, 7>::from_bytes(&bytes, builder());
+        assert_eq!(res.err(), Some(Error::IncompatibleSizes));
+
+        let res = BloomFilter::<u32, _, 50>::from_bytes(&bytes, builder());
+        assert_eq!(res.err(), Some(Error::IncompatibleSizes));
+
+        let other = BuildPairHasher::new_with_keys((2, 2), (3, 3));
+        let res = BloomFilter::<u32, _>::from_bytes(&bytes, other);
+        assert_eq!(res.err(), Some(Error::IncompatibleHashers));
+
+        let res = BloomFilter::<u32, _>::from_bytes(&bytes[1..], builder());
+        assert_eq!(res.err(), Some(Error::InvalidFormat("magic")));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::{
+    params,
+    thrift::{self, Reader, Writer},
+    xxhash::xxh64,
+    Error,
+};
+use std::io::{Read, Write};
+
+/// The number of bytes in a block, eight 32 bits words.
+pub const SPLIT_BLOCK_BYTES: usize = 32;
+
+/// The largest bitset written by the Parquet writers, 128 MiB.
+pub const SPLIT_BLOCK_MAX_BYTES: usize = 128 * 1024 * 1024;
+
+/// The odd constants which select the bit set in each word of a block.
+const SALT: [u32; 8] = [
+    0x47b6_137b,
+    0x4497_4d91,
+    0x8824_ad5b,
+    0xa2b7_289d,
+    0x7054_95c7,
+    0x2df1_424b,
+    0x9efc_4947,
+    0x5c6b_fb31,
+];
+
+/// A block of 256 bits, where each item sets one bit in each of the eight words.
+type Block = [u32; 8];
+
+fn block_mask(hash: u32) -> Block {
+    SALT.map(|salt| 1 << (hash.wrapping_mul(salt) >> 27))
+}
+
+/// A value of a Parquet column, hashed like the Parquet readers and writers hash it: the xxHash64, with a zero
+/// seed, of its plain encoding. The numbers are encoded as little-endian bytes, and the byte arrays and strings
+/// as their bytes, without the length prefix of the plain encoding.
+pub trait ParquetValue {
+    /// Returns the hash value of the value.
+    fn parquet_hash(&self) -> u64;
+}
+
+macro_rules! impl_parquet_number {
+    ($($ty:ty),*) => {
+        $(
+            impl ParquetValue for $ty {
+                fn parquet_hash(&self) -> u64 {
+                    xxh64(&self.to_le_bytes(), 0)
+                }
+            }
+        )*
+    };
+}
+
+// The unsigned integers are stored in the INT32 and INT64 physical types with the same bits.
+impl_parquet_number!(i32, i64, u32, u64, f32, f64);
+
+impl ParquetValue for [u8] {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self, 0)
+    }
+}
+
+impl<const N: usize> ParquetValue for [u8; N] {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self, 0)
+    }
+}
+
+impl ParquetValue for Vec<u8> {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self, 0)
+    }
+}
+
+impl ParquetValue for str {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self.as_bytes(), 0)
+    }
+}
+
+impl ParquetValue for String {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self.as_bytes(), 0)
+    }
+}
+
+impl<T: ParquetValue + ?Sized> ParquetValue for &T {
+    fn parquet_hash(&self) -> u64 {
+        (**self).parquet_hash()
+    }
+}
+
+/// Implements the split block bloom filter of the [Parquet format](https://github.com/apache/parquet-format/blob/master/BloomFilter.md),
+/// so the filters can be read from and written to the column chunks of Parquet files. The filter is an array of
+/// blocks of 256 bits. The upper half of the hash value of an item selects a block, and the lower half is multiplied
+/// by eight salts to set one bit in each 32 bits word of the block.
+///
+/// Unlike the other filters, the hashing is fixed by the format: the items are [`ParquetValue`]s hashed with
+/// xxHash64. [`SplitBlockBloomFilter::to_bytes`] writes the Thrift header and the bitset in the layout stored
+/// in the files, and [`SplitBlockBloomFilter::from_bytes`] reads it back.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::SplitBlockBloomFilter;
+///
+/// let mut filter = SplitBlockBloomFilter::with_capacity_and_fpr(1000, 0.01).unwrap();
+/// filter.insert("Hello world!");
+/// filter.insert(&42i64);
+///
+/// let bytes = filter.to_bytes();
+/// let filter = SplitBlockBloomFilter::from_bytes(&bytes).unwrap();
+/// assert!(filter.contains("Hello world!"));
+/// assert!(filter.contains(&42i64));
+///```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitBlockBloomFilter {
+    blocks: Vec<Block>,
+}
+
+impl SplitBlockBloomFilter {
+    /// Creates a new [`SplitBlockBloomFilter`] instance with at least `num_bytes` bytes. Like the Parquet writers,
+    /// the number of bytes is rounded up to a power of two between [`SPLIT_BLOCK_BYTES`] and [`SPLIT_BLOCK_MAX_BYTES`].
+    pub fn new(num_bytes: usize) -> Result<Self, Error> {
+        if num_bytes == 0 {
+            return Err(Error::ZeroSize("number of bytes"));
+        }
+
+        let num_bytes = num_bytes
+            .clamp(SPLIT_BLOCK_BYTES, SPLIT_BLOCK_MAX_BYTES)
+            .next_power_of_two();
+
+        Ok(Self {
+            blocks: vec![Block::default(); num_bytes / SPLIT_BLOCK_BYTES],
+        })
+    }
+
+    /// Creates a new [`SplitBlockBloomFilter`] instance sized like the Parquet writers size the filter of a column
+    /// with `items` distinct values and a false-positive probability `fpr`, see [`params::split_block_bits`].
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64) -> Result<Self, Error> {
+        if items == 0 {
+            return Err(Error::ZeroSize("number of items"));
+        }
+
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        Self::new((params::split_block_bits(items, fpr) / 8).max(1))
+    }
+
+    /// Creates a filter from its bitset, the little-endian words of the blocks stored in the Parquet files.
+    /// The bitset must be a whole number of blocks and not larger than [`SPLIT_BLOCK_MAX_BYTES`].
+    pub fn from_bitset(bitset: &[u8]) -> Result<Self, Error> {
+        check_num_bytes(bitset.len())?;
+
+        let blocks = bitset
+            .chunks_exact(SPLIT_BLOCK_BYTES)
+            .map(|block| {
+                let mut words = Block::default();
+                words
+                    .iter_mut()
+                    .zip(block.chunks_exact(4))
+                    .for_each(|(word, bytes)| {
+                        *word = u32::from_le_bytes(bytes.try_into().unwrap())
+                    });
+                words
+            })
+            .collect();
+
+        Ok(Self { blocks })
+    }
+
+    /// Returns the bitset of the filter, the little-endian words of the blocks stored in the Parquet files.
+    pub fn bitset(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flatten()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    /// Returns the number of bytes of the bitset.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * SPLIT_BLOCK_BYTES
+    }
+
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    /// Inserts in the filter a new value.
+    pub fn insert<V: ParquetValue + ?Sized>(&mut self, value: &V) {
+        self.insert_hash(value.parquet_hash());
+    }
+
+    /// Checks if a given value is present in the filter.
+    pub fn contains<V: ParquetValue + ?Sized>(&self, value: &V) -> bool {
+        self.contains_hash(value.parquet_hash())
+    }
+
+    /// Inserts in the filter a value given by its hash value, see [`ParquetValue::parquet_hash`].
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let block = &mut self.blocks[index];
+
+        block
+            .iter_mut()
+            .zip(block_mask(hash as u32))
+            .for_each(|(word, mask)| *word |= mask);
+    }
+
+    /// Checks if a value given by its hash value is present in the filter, see [`ParquetValue::parquet_hash`].
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        let block = &self.blocks[self.block_index(hash)];
+
+        block
+            .iter()
+            .zip(block_mask(hash as u32))
+            .all(|(word, mask)| word & mask != 0)
+    }
+
+    /// Serializes the filter in the layout of the Parquet files: the `BloomFilterHeader` Thrift structure in the
+    /// compact protocol, recording the number of bytes, the split block algorithm, the xxHash64 hashing and no
+    /// compression, followed by the bitset.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.i32(1, self.num_bytes() as i32);
+        [2, 3, 4].into_iter().for_each(|id| {
+            writer.begin_struct(id);
+            writer.begin_struct(1);
+            writer.end_struct();
+            writer.end_struct();
+        });
+
+        let mut bytes = writer.finish();
+        bytes.extend_from_slice(&self.bitset());
+        bytes
+    }
+
+    /// Deserializes a filter serialized by [`SplitBlockBloomFilter::to_bytes`] or by a Parquet writer, given the
+    /// bytes starting at the bloom filter offset of a column chunk. It fails when the bytes are not a valid
+    /// serialized filter or the filter uses another algorithm, hashing or compression.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = bytes;
+        let filter = Self::read_from(&mut reader)?;
+
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat("trailing bytes"));
+        }
+
+        Ok(filter)
+    }
+
+    /// Writes the serialized filter, see [`SplitBlockBloomFilter::to_bytes`].
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a filter written by [`SplitBlockBloomFilter::write_to`], see [`SplitBlockBloomFilter::from_bytes`].
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let num_bytes = read_header(&mut reader)?;
+        check_num_bytes(num_bytes)?;
+
+        let mut bitset = vec![0u8; num_bytes];
+        reader.read_exact(&mut bitset)?;
+        Self::from_bitset(&bitset)
+    }
+}
+
+fn check_num_bytes(num_bytes: usize) -> Result<(), Error> {
+    if num_bytes == 0
+        || !num_bytes.is_multiple_of(SPLIT_BLOCK_BYTES)
+        || num_bytes > SPLIT_BLOCK_MAX_BYTES
+    {
+        return Err(Error::InvalidFormat("bitset length"));
+    }
+
+    Ok(())
+}
+
+/// Reads a `BloomFilterHeader` and returns the number of bytes of the bitset.
+fn read_header<R: Read>(reader: R) -> Result<usize, Error> {
+    let mut reader = Reader::new(reader);
+    let mut num_bytes = None;
+    let mut variants = [false; 3];
+
+    while let Some((id, ty)) = reader.field()? {
+        match (id, ty) {
+            (1, thrift::I32) => {
+                let value = reader.i32()?;
+                num_bytes = Some(
+                    usize::try_from(value).map_err(|_| Error::InvalidFormat("bitset length"))?,
+                );
+            }
+            (2..=4, thrift::STRUCT) => {
+                variants[id as usize - 2] = read_union(&mut reader)?;
+            }
+            _ => reader.skip(ty)?,
+        }
+    }
+
+    if !variants[0] {
+        return Err(Error::InvalidFormat("bloom filter algorithm"));
+    }
+
+    if !variants[1] {
+        return Err(Error::InvalidFormat("bloom filter hash"));
+    }
+
+    if !variants[2] {
+        return Err(Error::InvalidFormat("bloom filter compression"));
+    }
+
+    num_bytes.ok_or(Error::InvalidFormat("bitset length"))
+}
+
+/// Reads one of the unions of the header and checks that its variant is the first one, the only one
+/// defined by the format for the algorithm, the hashing and the compression.
+fn read_union<R: Read>(reader: &mut Reader<R>) -> Result<bool, Error> {
+    let mut variants = 0;
+    let mut first = true;
+
+    reader.begin_struct();
+    while let Some((id, ty)) = reader.field()? {
+        variants += 1;
+        first &= id == 1 && ty == thrift::STRUCT;
+        reader.skip(ty)?;
+    }
+    reader.end_struct();
+
+    Ok(variants == 1 && first)
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{de_error, Bytes};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`SplitBlockBloomFilter`], its bitset.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        bitset: Bytes,
+    }
+
+    impl Serialize for SplitBlockBloomFilter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                bitset: Bytes(self.bitset()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SplitBlockBloomFilter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_bitset(&repr.bitset.0).map_err(de_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of a filter of 32 bytes written by the Parquet writers.
+    const HEADER: [u8; 15] = [21, 64, 28, 28, 0, 0, 28, 28, 0, 0, 28, 28, 0, 0, 0];
+
+    /// The bitset written by parquet-mr for a column with the values `a0` to `a9`.
+    const BITSET: [u8; 32] = [
+        200, 1, 80, 20, 64, 68, 8, 109, 6, 37, 4, 67, 144, 80, 96, 32, 8, 132, 43, 33, 0, 5, 99,
+        65, 2, 0, 224, 44, 64, 78, 96, 4,
+    ];
+
+    #[test]
+    fn golden_bitset() {
+        let mut filter = SplitBlockBloomFilter::new(32).unwrap();
+        (0..10).for_each(|i| filter.insert(format!("a{i}").as_str()));
+        assert_eq!(filter.bitset(), BITSET);
+
+        let filter = SplitBlockBloomFilter::from_bitset(&BITSET).unwrap();
+        assert!((0..10).all(|i| filter.contains(&format!("a{i}"))));
+        assert!(!filter.contains("b0"));
+    }
+
+    #[test]
+    fn golden_bytes() {
+        let filter = SplitBlockBloomFilter::from_bitset(&BITSET).unwrap();
+        let bytes = filter.to_bytes();
+        assert_eq!(bytes[..HEADER.len()], HEADER);
+        assert_eq!(bytes[HEADER.len()..], BITSET);
+
+        assert_eq!(SplitBlockBloomFilter::from_bytes(&bytes).unwrap(), filter);
+    }
+
+    #[test]
+    fn sizes() {
+        let num_bytes = |n| SplitBlockBloomFilter::new(n).unwrap().num_bytes();
+        assert_eq!(num_bytes(1), 32);
+        assert_eq!(num_bytes(31), 32);
+        assert_eq!(num_bytes(32), 32);
+        assert_eq!(num_bytes(33), 64);
+        assert_eq!(num_bytes(99), 128);
+        assert_eq!(num_bytes(1024), 1024);
+        assert_eq!(num_bytes(999_000_000), SPLIT_BLOCK_MAX_BYTES);
+        assert_eq!(
+            SplitBlockBloomFilter::new(0).err(),
+            Some(Error::ZeroSize("number of bytes"))
+        );
+
+        assert_eq!(params::split_block_bits(1000, 0.01), 9681);
+        assert_eq!(params::split_block_bits(10, 0.1), 57);
+        assert_eq!(params::split_block_bits(100, 0.01), 968);
+    }
+
+    #[test]
+    fn insert_contains() {
+        let mut filter = SplitBlockBloomFilter::with_capacity_and_fpr(1000, 0.01).unwrap();
+        (0..1000i64).for_each(|i| filter.insert(&i));
+        assert!((0..1000i64).all(|i| filter.contains(&i)));
+
+        let false_positives = (1000..11_000i64).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 150);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let filter = SplitBlockBloomFilter::new(64).unwrap();
+        let bytes = filter.to_bytes();
+
+        let res = SplitBlockBloomFilter::from_bytes(&bytes[..bytes.len() - 1]);
+        assert_eq!(
+            res.err(),
+            Some(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+
+        let mut longer = bytes.clone();
+        longer.push(0);
+        let res = SplitBlockBloomFilter::from_bytes(&longer);
+        assert_eq!(res.err(), Some(Error::InvalidFormat("trailing bytes")));
+
+        // The hash union holds its second variant.
+        let mut other_hash = bytes.clone();
+        other_hash[7] = 0x2c;
+        let res = SplitBlockBloomFilter::from_bytes(&other_hash);
+        assert_eq!(res.err(), Some(Error::InvalidFormat("bloom filter hash")));
+
+        let res = SplitBlockBloomFilter::from_bitset(&[0; 48]);
+        assert_eq!(res.err(), Some(Error::InvalidFormat("bitset length")));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{bit_index, builder_fingerprint, params, Counters, Counters4, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Implements the [stable bloom filter](https://doi.org/10.1145/1142473.1142477) of Deng and Rafiei,
+/// which detects duplicates in an unbounded stream of items. Each insertion first decrements a number of
+/// randomly chosen counters and then sets the counters of the item to their maximum value, so the old
+/// items are evicted and the fraction of zero counters converges to a stable point instead of going to zero.
+/// The price is that an item inserted long ago may be reported as absent.
+///
+/// The random choices are seeded from the hasher builder, so two filters with the same builder and the
+/// same stream of items have the same state.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::StableBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = StableBloomFilter::<u64, _>::with_stable_fpr(10_000, 3, 3, 0.01, builder).unwrap();
+///
+/// (0..100_000u64).for_each(|i| filter.insert(&i));
+///
+/// assert!(filter.contains(&99_999));
+/// assert!(filter.stable_fpr() <= 0.01);
+///```
+pub struct StableBloomFilter<T, B, C = Counters4>
+where
+    T: ?Sized,
+{
+    builder: B,
+    counters: C,
+    hashes: usize,
+    max: u16,
+    decrements: usize,
+    rng: ChaCha12Rng,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B, C> StableBloomFilter<T, B, C>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    C: Counters,
+{
+    /// Creates a new [`StableBloomFilter`] instance with a given number of counters, hash values for each item,
+    /// maximum value of the counters and number of counters decremented on each insertion.
+    pub fn new(
+        cells: usize,
+        hashes: usize,
+        max: u16,
+        decrements: usize,
+        builder: B,
+    ) -> Result<Self, Error> {
+        if cells == 0 {
+            return Err(Error::ZeroSize("number of counters"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        if decrements == 0 {
+            return Err(Error::ZeroSize("number of decrements"));
+        }
+
+        if max == 0 || max > C::MAX {
+            return Err(Error::InvalidCounterMax(max));
+        }
+
+        let rng = ChaCha12Rng::seed_from_u64(builder_fingerprint(&builder));
+
+        Ok(Self {
+            builder,
+            counters: C::with_len(cells),
+            hashes,
+            max,
+            decrements,
+            rng,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a new [`StableBloomFilter`] instance which decrements enough counters on each insertion
+    /// to keep the false-positive probability at the stable point under `fpr`.
+    pub fn with_stable_fpr(
+        cells: usize,
+        hashes: usize,
+        max: u16,
+        fpr: f64,
+        builder: B,
+    ) -> Result<Self, Error> {
+        if !(fpr > 0.0 && fpr < 1.0) {
+            return Err(Error::InvalidProbability(fpr));
+        }
+
+        let decrements = params::stable_decrements(cells, hashes, max, fpr);
+        Self::new(cells, hashes, max, decrements, builder)
+    }
+
+    /// Returns the number of counters in the filter.
+    pub fn cells(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Returns the number of hash values computed for each item.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the value the counters of an item are set to when the item is inserted.
+    pub fn max(&self) -> u16 {
+        self.max
+    }
+
+    /// Returns the number of counters decremented on each insertion.
+    pub fn decrements(&self) -> usize {
+        self.decrements
+    }
+
+    /// Returns the false-positive probability once the filter reaches its stable point.
+    pub fn stable_fpr(&self) -> f64 {
+        params::stable_false_positive_rate(self.cells(), self.hashes, self.max, self.decrements)
+    }
+
+    /// Returns the current fraction of counters which are zero.
+    pub fn zero_ratio(&self) -> f64 {
+        let zeros = (0..self.counters.len())
+            .filter(|index| self.counters.get(*index) == 0)
+            .count();
+
+        zeros as f64 / self.counters.len() as f64
+    }
+
+    fn decrement_random(&mut self) {
+        // Decrementing the counters which follow a random one has the same stable point as decrementing
+        // randomly chosen counters and it draws a single random number.
+        let len = self.counters.len();
+        let start = self.rng.gen_range(0..len);
+
+        (0..self.decrements).for_each(|i| self.counters.decrement((start + i) % len));
+    }
+}
+
+impl<T, B, C> StableBloomFilter<T, B, C>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+    C: Counters,
+{
+    /// Inserts in the filter a new item.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        self.decrement_random();
+
+        let len = self.counters.len();
+        let max = self.max;
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .for_each(|hash| self.counters.set(bit_index(hash, len), max));
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let len = self.counters.len();
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .all(|hash| self.counters.get(bit_index(hash, len)) > 0)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, check_size, counter_bits, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`StableBloomFilter`]. The random generator is seeded from the hasher builder,
+    /// so its position is enough to restore it.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        counter_bits: u32,
+        cells: usize,
+        hashes: usize,
+        max: u16,
+        decrements: usize,
+        rng_word_pos: u128,
+        counters: Bytes,
+    }
+
+    impl<T, B, C> Serialize for StableBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                counter_bits: counter_bits::<C>(),
+                cells: self.counters.len(),
+                hashes: self.hashes,
+                max: self.max,
+                decrements: self.decrements,
+                rng_word_pos: self.rng.get_word_pos(),
+                counters: Bytes::from_counters(&self.counters),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B, C> Deserialize<'de> for StableBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B, C> StableBloomFilter<T, B, C>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        C: Counters,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the counters have a different width or when the filter was built with a different
+        /// hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_size(repr.counter_bits, counter_bits::<C>())?;
+            check_hasher(repr.hasher, &builder)?;
+
+            let counters = repr.counters.into_counters(repr.cells)?;
+            let mut filter =
+                Self::new(repr.cells, repr.hashes, repr.max, repr.decrements, builder)?;
+            filter.counters = counters;
+            filter.rng.set_word_pos(repr.rng_word_pos);
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Counters8;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn converges_to_stable_point() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter = StableBloomFilter::<u64, _>::new(1000, 3, 3, 10, builder).unwrap();
+
+        (0..100_000u64).for_each(|i| filter.insert(&i));
+
+        // At the stable point, the probability of a counter being zero is `1 - fpr^(1/k)`.
+        let zero_ratio = 1.0 - filter.stable_fpr().powf(1.0 / 3.0);
+        assert!((filter.zero_ratio() - zero_ratio).abs() < 0.05);
+
+        let false_positives = (100_000..110_000u64).filter(|i| filter.contains(i)).count();
+        let fpr = false_positives as f64 / 10_000.0;
+        assert!((fpr - filter.stable_fpr()).abs() < 0.02);
+    }
+
+    #[test]
+    fn invalid_max() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = StableBloomFilter::<u64, _>::new(1000, 3, 16, 10, builder);
+        assert_eq!(res.err(), Some(Error::InvalidCounterMax(16)));
+
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = StableBloomFilter::<u64, _, Counters8>::new(1000, 3, 16, 10, builder);
+        assert!(res.is_ok());
+    }
+}
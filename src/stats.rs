@@ -0,0 +1,63 @@
+use crate::params;
+
+/// A snapshot of the saturation of a bloom filter, returned by [`crate::BloomFilter::stats`].
+/// The false-positive probability grows with the fill ratio, so the filter should be rebuilt
+/// when it is too high.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::*;
+/// use aabel_multihash_rs::*;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = BloomFilter::<u32, _, 10, 4>::new(builder);
+///
+/// (0..1000u32).for_each(|i| filter.insert(&i));
+///
+/// let stats = filter.stats();
+/// assert!(stats.fill_ratio > 0.9);
+/// assert!(stats.fpr > 0.5);
+///```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// The number of bits of the filter.
+    pub bits: usize,
+    /// The number of bits set.
+    pub ones: usize,
+    /// The fraction of the bits which are set.
+    pub fill_ratio: f64,
+    /// The estimated number of distinct items inserted in the filter.
+    pub estimated_items: f64,
+    /// The probability that an item which was not inserted is reported as present,
+    /// computed as the fill ratio raised to the number of hash values for each item.
+    pub fpr: f64,
+}
+
+impl Stats {
+    pub(crate) fn new(bits: usize, hashes: usize, ones: usize) -> Self {
+        let fill_ratio = ones as f64 / bits as f64;
+
+        Self {
+            bits,
+            ones,
+            fill_ratio,
+            estimated_items: params::estimated_items(bits, hashes, ones),
+            fpr: fill_ratio.powi(hashes as i32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let stats = Stats::new(1000, 2, 500);
+
+        assert_eq!(stats.fill_ratio, 0.5);
+        assert_eq!(stats.fpr, 0.25);
+        assert!((stats.estimated_items - 346.57).abs() < 0.01);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{builder_fingerprint, invertible_bloom_filter::Table, xor_filter::mix, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use std::{
+    hash::{BuildHasher, Hash},
+    ops::BitXor,
+};
+
+/// The default number of strata.
+pub const DEFAULT_STRATA: usize = 32;
+
+/// The default number of cells of the invertible table of each stratum.
+pub const DEFAULT_STRATUM_CELLS: usize = 80;
+
+/// The number of hash values computed for each item in the table of a stratum.
+const STRATUM_HASHES: usize = 4;
+
+/// The constant mixed with the hash value of an item to select its stratum.
+const STRATUM_SEED: u64 = 0x5851_f42d_4c95_7f2d;
+
+/// Implements the strata estimator of [Eppstein et al.](https://doi.org/10.1145/2018436.2018462), which estimates
+/// the size of the difference of two sets before reconciling them with an
+/// [`InvertibleBloomFilter`](crate::InvertibleBloomFilter). Each item is inserted in the small invertible table
+/// of the i-th stratum, where `i` is the number of trailing zeros of its hash value, so the i-th stratum samples
+/// a `2^-(i+1)` fraction of the items.
+///
+/// After subtracting the estimators of the two sets, the strata are decoded from the last one. When the i-th
+/// stratum cannot be decoded, the items decoded from the strata after it are scaled by `2^(i+1)`.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::StrataEstimator;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut a = StrataEstimator::<u64, _>::new(builder());
+/// let mut b = StrataEstimator::<u64, _>::new(builder());
+///
+/// (0..10_000u64).for_each(|i| a.insert(&i));
+/// (10..10_010u64).for_each(|i| b.insert(&i));
+///
+/// a.subtract(&b).unwrap();
+/// assert_eq!(a.estimate(), 20);
+///```
+pub struct StrataEstimator<T, B> {
+    builder: B,
+    strata: Vec<Table<T>>,
+}
+
+impl<T, B> StrataEstimator<T, B>
+where
+    T: Copy + Default + Eq + Hash + BitXor<Output = T>,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`StrataEstimator`] instance with [`DEFAULT_STRATA`] strata of [`DEFAULT_STRATUM_CELLS`] cells.
+    pub fn new(builder: B) -> Self {
+        let slice_len = DEFAULT_STRATUM_CELLS.div_ceil(STRATUM_HASHES);
+
+        Self {
+            builder,
+            strata: vec![Table::new(slice_len, STRATUM_HASHES); DEFAULT_STRATA],
+        }
+    }
+
+    /// Creates a new [`StrataEstimator`] instance with a given number of strata and a given number of cells
+    /// for each stratum.
+    pub fn with_strata(strata: usize, cells: usize, builder: B) -> Result<Self, Error> {
+        if strata == 0 {
+            return Err(Error::ZeroSize("number of strata"));
+        }
+
+        if cells == 0 {
+            return Err(Error::ZeroSize("number of cells"));
+        }
+
+        let slice_len = cells.div_ceil(STRATUM_HASHES);
+
+        Ok(Self {
+            builder,
+            strata: vec![Table::new(slice_len, STRATUM_HASHES); strata],
+        })
+    }
+
+    /// Returns the number of strata.
+    pub fn strata(&self) -> usize {
+        self.strata.len()
+    }
+
+    /// Returns the number of cells of each stratum.
+    pub fn stratum_cells(&self) -> usize {
+        self.strata[0].len()
+    }
+
+    /// Inserts in the estimator a new item.
+    pub fn insert(&mut self, item: &T) {
+        let stratum = self.stratum(item);
+        self.strata[stratum].update(item, 1, &self.builder);
+    }
+
+    /// Removes an item from the estimator.
+    pub fn remove(&mut self, item: &T) {
+        let stratum = self.stratum(item);
+        self.strata[stratum].update(item, -1, &self.builder);
+    }
+
+    /// Subtracts from the estimator the items of another estimator. It fails when the estimators have
+    /// different sizes or when they are built with different hasher builders.
+    pub fn subtract(&mut self, other: &Self) -> Result<(), Error> {
+        let same_shape = self.strata.len() == other.strata.len()
+            && self.strata[0].has_same_shape(&other.strata[0]);
+        if !same_shape {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        self.strata
+            .iter_mut()
+            .zip(&other.strata)
+            .for_each(|(x, y)| x.subtract(y));
+
+        Ok(())
+    }
+
+    /// Estimates the number of items in the estimator, which after `a.subtract(&b)` is the size of the
+    /// symmetric difference of the two sets. The estimate is exact when all the strata can be decoded.
+    pub fn estimate(&self) -> usize {
+        let mut count = 0usize;
+
+        for (stratum, table) in self.strata.iter().enumerate().rev() {
+            match table.decode(&self.builder) {
+                Ok(difference) => {
+                    count += difference.only_in_self.len() + difference.only_in_other.len()
+                }
+                Err(_) => return count.saturating_mul(2usize.saturating_pow(stratum as u32 + 1)),
+            }
+        }
+
+        count
+    }
+
+    fn stratum(&self, item: &T) -> usize {
+        let hash = mix(self.builder.hash_one(item), STRATUM_SEED);
+        (hash.trailing_zeros() as usize).min(self.strata.len() - 1)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::serde_support::{check_hasher, de_error};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`StrataEstimator`], which borrows the strata when serializing
+    /// and owns them when deserializing.
+    #[derive(Serialize, Deserialize)]
+    struct Repr<S> {
+        hasher: u64,
+        strata: S,
+    }
+
+    impl<T, B> Serialize for StrataEstimator<T, B>
+    where
+        T: Copy + Serialize,
+        B: BuildHasher,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                strata: &self.strata,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for StrataEstimator<T, B>
+    where
+        T: Copy + Default + Eq + Hash + BitXor<Output = T> + Deserialize<'de>,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> StrataEstimator<T, B>
+    where
+        T: Copy + Default + Eq + Hash + BitXor<Output = T>,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes an estimator with a given hasher builder, for the builders which do not implement
+        /// [`Default`]. It fails when the estimator was built with a different hasher builder.
+        pub fn deserialize_with<'de, D>(deserializer: D, builder: B) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de>,
+        {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr<Vec<Table<T>>>, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            let first = repr
+                .strata
+                .first()
+                .ok_or(Error::InvalidFormat("no strata"))?;
+
+            for table in &repr.strata {
+                table.check_shape()?;
+
+                if !table.has_same_shape(first) {
+                    return Err(Error::InvalidFormat("strata of different sizes"));
+                }
+            }
+
+            Ok(Self {
+                builder,
+                strata: repr.strata,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn estimate() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+
+        for difference in [100u64, 1000, 5000] {
+            let mut a = StrataEstimator::<u64, _>::new(builder());
+            let mut b = StrataEstimator::<u64, _>::new(builder());
+
+            (0..20_000u64).for_each(|i| a.insert(&i));
+            (difference / 2..20_000 + difference / 2).for_each(|i| b.insert(&i));
+
+            a.subtract(&b).unwrap();
+            let estimate = a.estimate() as f64;
+            assert!(estimate > 0.5 * difference as f64 && estimate < 2.0 * difference as f64);
+        }
+    }
+
+    #[test]
+    fn incompatible() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut a = StrataEstimator::<u64, _>::new(builder());
+
+        let b = StrataEstimator::<u64, _>::with_strata(16, 80, builder()).unwrap();
+        assert_eq!(a.subtract(&b), Err(Error::IncompatibleSizes));
+
+        let b = StrataEstimator::<u64, _>::new(BuildPairHasher::new_with_keys((2, 2), (3, 3)));
+        assert_eq!(a.subtract(&b), Err(Error::IncompatibleHashers));
+    }
+}
@@ -0,0 +1,281 @@
+//! A minimal implementation of the [Thrift compact protocol](https://github.com/apache/thrift/blob/master/doc/specs/thrift-compact-protocol.md),
+//! enough to write and read the structures which describe the filters stored by other systems.
+
+use crate::Error;
+use std::io::Read;
+
+/// The type of a field holding a 32 bits integer.
+pub(crate) const I32: u8 = 5;
+
+/// The type of a field holding a nested structure.
+pub(crate) const STRUCT: u8 = 12;
+
+/// The deepest nesting of structures and containers skipped by [`Reader::skip`].
+const MAX_DEPTH: usize = 32;
+
+/// Writes a structure field by field. The nested structures are opened with [`Writer::begin_struct`]
+/// and closed with [`Writer::end_struct`], which keep track of the last field id of each structure.
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+    last_id: i16,
+    stack: Vec<i16>,
+}
+
+impl Writer {
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            last_id: 0,
+            stack: Vec::new(),
+        }
+    }
+
+    fn field(&mut self, id: i16, ty: u8) {
+        let delta = id.wrapping_sub(self.last_id);
+        if (1..=15).contains(&delta) {
+            self.bytes.push((delta as u8) << 4 | ty);
+        } else {
+            self.bytes.push(ty);
+            self.varint(zigzag(id as i64));
+        }
+
+        self.last_id = id;
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+
+        self.bytes.push(value as u8);
+    }
+
+    /// Writes a field holding a 32 bits integer.
+    pub(crate) fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.varint(zigzag(value as i64));
+    }
+
+    /// Starts a field holding a nested structure.
+    pub(crate) fn begin_struct(&mut self, id: i16) {
+        self.field(id, STRUCT);
+        self.stack.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    /// Ends the nested structure started by the last [`Writer::begin_struct`].
+    pub(crate) fn end_struct(&mut self) {
+        self.bytes.push(0);
+        self.last_id = self.stack.pop().expect("a structure was started");
+    }
+
+    /// Ends the outermost structure and returns its bytes.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        debug_assert!(self.stack.is_empty());
+        self.bytes.push(0);
+        self.bytes
+    }
+}
+
+/// Reads a structure field by field. [`Reader::field`] returns the id and the type of the next field,
+/// whose value is then read with the function matching its type or skipped with [`Reader::skip`].
+pub(crate) struct Reader<R> {
+    reader: R,
+    last_id: i16,
+    stack: Vec<i16>,
+}
+
+impl<R: Read> Reader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            last_id: 0,
+            stack: Vec::new(),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [0u8];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::InvalidFormat("thrift varint"))
+    }
+
+    /// Returns the id and the type of the next field, or `None` at the end of the structure.
+    pub(crate) fn field(&mut self) -> Result<Option<(i16, u8)>, Error> {
+        let byte = self.byte()?;
+        if byte == 0 {
+            return Ok(None);
+        }
+
+        let (delta, ty) = (byte >> 4, byte & 0x0f);
+        let id = if delta == 0 {
+            i16::try_from(unzigzag(self.varint()?))
+                .map_err(|_| Error::InvalidFormat("thrift field id"))?
+        } else {
+            self.last_id.wrapping_add(delta as i16)
+        };
+
+        self.last_id = id;
+        Ok(Some((id, ty)))
+    }
+
+    /// Reads the value of a field holding a 32 bits integer.
+    pub(crate) fn i32(&mut self) -> Result<i32, Error> {
+        i32::try_from(unzigzag(self.varint()?)).map_err(|_| Error::InvalidFormat("thrift i32"))
+    }
+
+    /// Starts reading the value of a field holding a nested structure.
+    pub(crate) fn begin_struct(&mut self) {
+        self.stack.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    /// Ends reading the nested structure, once [`Reader::field`] returned `None`.
+    pub(crate) fn end_struct(&mut self) {
+        self.last_id = self.stack.pop().expect("a structure was started");
+    }
+
+    /// Skips the value of a field of a given type.
+    pub(crate) fn skip(&mut self, ty: u8) -> Result<(), Error> {
+        self.skip_value(ty, 0)
+    }
+
+    fn skip_value(&mut self, ty: u8, depth: usize) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidFormat("thrift nesting"));
+        }
+
+        match ty {
+            // The booleans of the fields are stored in their type.
+            1 | 2 => {}
+            3 => {
+                self.byte()?;
+            }
+            4..=6 => {
+                self.varint()?;
+            }
+            7 => {
+                let mut double = [0u8; 8];
+                self.reader.read_exact(&mut double)?;
+            }
+            8 => {
+                let len = self.varint()?;
+                let skipped =
+                    std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+                if skipped != len {
+                    return Err(Error::Io(std::io::ErrorKind::UnexpectedEof));
+                }
+            }
+            9 | 10 => {
+                let header = self.byte()?;
+                let len = match header >> 4 {
+                    15 => self.varint()?,
+                    len => len as u64,
+                };
+
+                for _ in 0..len {
+                    self.skip_element(header & 0x0f, depth + 1)?;
+                }
+            }
+            11 => {
+                let len = self.varint()?;
+                if len > 0 {
+                    let types = self.byte()?;
+                    for _ in 0..len {
+                        self.skip_element(types >> 4, depth + 1)?;
+                        self.skip_element(types & 0x0f, depth + 1)?;
+                    }
+                }
+            }
+            STRUCT => {
+                self.begin_struct();
+                while let Some((_, ty)) = self.field()? {
+                    self.skip_value(ty, depth + 1)?;
+                }
+                self.end_struct();
+            }
+            _ => return Err(Error::InvalidFormat("thrift type")),
+        }
+
+        Ok(())
+    }
+
+    /// Skips an element of a container, where the booleans take a byte.
+    fn skip_element(&mut self, ty: u8, depth: usize) -> Result<(), Error> {
+        match ty {
+            1 | 2 => self.byte().map(|_| ()),
+            _ => self.skip_value(ty, depth),
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let mut writer = Writer::new();
+        writer.i32(1, -3);
+        writer.begin_struct(2);
+        writer.i32(20, 300);
+        writer.end_struct();
+        writer.i32(3, 7);
+        let bytes = writer.finish();
+        assert_eq!(
+            bytes,
+            vec![0x15, 0x05, 0x1c, 0x05, 0x28, 0xd8, 0x04, 0x00, 0x15, 0x0e, 0x00]
+        );
+
+        let mut reader = Reader::new(&bytes[..]);
+        assert_eq!(reader.field().unwrap(), Some((1, I32)));
+        assert_eq!(reader.i32().unwrap(), -3);
+        assert_eq!(reader.field().unwrap(), Some((2, STRUCT)));
+        reader.skip(STRUCT).unwrap();
+        assert_eq!(reader.field().unwrap(), Some((3, I32)));
+        assert_eq!(reader.i32().unwrap(), 7);
+        assert_eq!(reader.field().unwrap(), None);
+    }
+
+    #[test]
+    fn skip_containers() {
+        // A list of two strings, a map from i32 to booleans, and a double.
+        let bytes = [
+            0x29, 0x28, 0x01, b'a', 0x02, b'b', b'c', 0x1b, 0x01, 0x51, 0x04, 0x01, 0x17, 0, 0, 0,
+            0, 0, 0, 0xf0, 0x3f, 0x00,
+        ];
+
+        let mut reader = Reader::new(&bytes[..]);
+        while let Some((_, ty)) = reader.field().unwrap() {
+            reader.skip(ty).unwrap();
+        }
+
+        assert_eq!(Reader::new(&[0x1d][..]).field().unwrap(), Some((1, 13)));
+        assert_eq!(
+            Reader::new(&[0x0d][..]).skip(13).err(),
+            Some(Error::InvalidFormat("thrift type"))
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{
+    bit_index, builder_fingerprint, intersect_raw, params, union_count_ones, union_raw, Error,
+    Stats,
+};
+use aabel_multihash_rs::{BuildHasherExt, Hash64, HasherExt};
+use bitvec::vec::BitVec;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    ops::{BitAnd, BitOr},
+};
+
+/// Implements the [bloom filter](https://en.wikipedia.org/wiki/Bloom_filter) over a heap allocated bit vector.
+/// Unlike [`crate::BloomFilter`], the number of bits and the number of hash values computed for each item
+/// are chosen at runtime, for example from the expected number of items and a target false-positive rate.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::VecBloomFilter;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let mut filter = VecBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+///
+/// filter.insert("Hello world!");
+/// assert!(filter.contains("Hello world!"));
+///```
+pub struct VecBloomFilter<T, B>
+where
+    T: ?Sized,
+{
+    builder: B,
+    bits: BitVec,
+    hashes: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, B> VecBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    /// Creates a new [`VecBloomFilter`] instance with a given number of bits and hash values for each item.
+    pub fn new(bits: usize, hashes: usize, builder: B) -> Result<Self, Error> {
+        if bits == 0 {
+            return Err(Error::ZeroSize("number of bits"));
+        }
+
+        if hashes == 0 {
+            return Err(Error::ZeroSize("number of hashes"));
+        }
+
+        Ok(Self {
+            builder,
+            bits: BitVec::repeat(false, bits),
+            hashes,
+            _marker: PhantomData,
+        })
+    }
+
+    pub(crate) fn from_bits(bits: BitVec, hashes: usize, builder: B) -> Self {
+        Self {
+            builder,
+            bits,
+            hashes,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new [`VecBloomFilter`] instance sized to hold `items` items while keeping
+    /// the false-positive probability under `fpr`. The parameters are the ones of [`params::Params::solve`]:
+    /// the optimal number of hash values `k = -log2(p)`, rounded, and the least number of bits which meets `fpr`
+    /// with `k` hash values.
+    pub fn with_capacity_and_fpr(items: usize, fpr: f64, builder: B) -> Result<Self, Error> {
+        let solution = params::Params::new().items(items).fpr(fpr).solve()?;
+        Self::new(solution.bits, solution.hashes, builder)
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn bit_len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Returns the number of hash values computed for each item.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the number of bits set in the filter.
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    /// Estimates the number of distinct items inserted in the filter from the number of bits set.
+    pub fn estimated_len(&self) -> f64 {
+        params::estimated_items(self.bits.len(), self.hashes, self.count_ones())
+    }
+
+    /// Returns the saturation of the filter and its current false-positive probability.
+    pub fn stats(&self) -> Stats {
+        Stats::new(self.bits.len(), self.hashes, self.count_ones())
+    }
+
+    /// Estimates the number of distinct items in the union of the items inserted in two filters.
+    /// It returns an error when the filters have different sizes or hasher builders.
+    pub fn estimated_union_len(&self, other: &Self) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+
+        let ones = union_count_ones(self.bits.as_raw_slice(), other.bits.as_raw_slice());
+        Ok(params::estimated_items(self.bits.len(), self.hashes, ones))
+    }
+
+    /// Estimates the number of distinct items inserted in both filters.
+    /// It returns an error when the filters have different sizes or hasher builders.
+    pub fn estimated_intersection_len(&self, other: &Self) -> Result<f64, Error> {
+        let union = self.estimated_union_len(other)?;
+        Ok((self.estimated_len() + other.estimated_len() - union).max(0.0))
+    }
+
+    /// Adds to the filter the items inserted in another filter.
+    /// It returns an error when the filters have different sizes or hasher builders.
+    pub fn union_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+
+        union_raw(self.bits.as_raw_mut_slice(), other.bits.as_raw_slice());
+        Ok(())
+    }
+
+    /// Keeps in the filter only the bits which are also set in another filter.
+    /// It returns an error when the filters have different sizes or hasher builders.
+    pub fn intersect_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)?;
+
+        intersect_raw(self.bits.as_raw_mut_slice(), other.bits.as_raw_slice());
+        Ok(())
+    }
+
+    /// Returns the union of two filters, see [`VecBloomFilter::union_with`].
+    pub fn union(mut self, other: &Self) -> Result<Self, Error> {
+        self.union_with(other)?;
+        Ok(self)
+    }
+
+    /// Returns the intersection of two filters, see [`VecBloomFilter::intersect_with`].
+    pub fn intersect(mut self, other: &Self) -> Result<Self, Error> {
+        self.intersect_with(other)?;
+        Ok(self)
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), Error> {
+        if self.bits.len() != other.bits.len() || self.hashes != other.hashes {
+            return Err(Error::IncompatibleSizes);
+        }
+
+        if builder_fingerprint(&self.builder) != builder_fingerprint(&other.builder) {
+            return Err(Error::IncompatibleHashers);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, B> VecBloomFilter<T, B>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+{
+    /// Inserts in the filter a new item.
+    pub fn insert<U>(&mut self, item: &U)
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let len = self.bits.len();
+        let set_bit_for_hash = |hash: Hash64| self.bits.set(bit_index(hash, len), true);
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .for_each(set_bit_for_hash);
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let get_bit_for_hash = |hash: Hash64| self.bits[bit_index(hash, self.bits.len())];
+
+        self.builder
+            .hashes_one(item)
+            .take(self.hashes)
+            .all(get_bit_for_hash)
+    }
+}
+
+impl<T, B> BitOr for VecBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    type Output = Result<Self, Error>;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(&rhs)
+    }
+}
+
+impl<T, B> BitAnd for VecBloomFilter<T, B>
+where
+    T: ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    type Output = Result<Self, Error>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersect(&rhs)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`VecBloomFilter`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        bit_len: usize,
+        hashes: usize,
+        bits: Bytes,
+    }
+
+    impl<T, B> Serialize for VecBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                bit_len: self.bits.len(),
+                hashes: self.hashes,
+                bits: Bytes::from_bits(&self.bits),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B> Deserialize<'de> for VecBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B> VecBloomFilter<T, B>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the filter was built with a different hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_hasher(repr.hasher, &builder)?;
+
+            let bits = repr.bits.into_bits(repr.bit_len)?;
+            let mut filter = Self::new(repr.bit_len, repr.hashes, builder)?;
+            filter.bits = bits;
+
+            Ok(filter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn with_capacity_and_fpr() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = VecBloomFilter::<str, _>::with_capacity_and_fpr(1000, 0.01, builder).unwrap();
+
+        assert_eq!(filter.bit_len(), 9593);
+        assert_eq!(filter.hashes(), 7);
+        assert!(params::false_positive_rate(filter.bit_len(), filter.hashes(), 1000) <= 0.01);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = VecBloomFilter::<str, _>::with_capacity_and_fpr(1000, 1.0, builder);
+        assert_eq!(res.err(), Some(Error::InvalidProbability(1.0)));
+
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let res = VecBloomFilter::<str, _>::new(0, 7, builder);
+        assert_eq!(res.err(), Some(Error::ZeroSize("number of bits")));
+    }
+
+    #[test]
+    fn insert_contains() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let mut filter =
+            VecBloomFilter::<str, _>::with_capacity_and_fpr(100, 0.01, builder).unwrap();
+
+        for i in 0..100 {
+            filter.insert(format!("item-{i}").as_str());
+        }
+
+        assert!((0..100).all(|i| filter.contains(format!("item-{i}").as_str())));
+        assert!((filter.estimated_len() - 100.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn incompatible_sizes() {
+        let builder = || BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter1 = VecBloomFilter::<str, _>::new(1000, 3, builder()).unwrap();
+        let filter2 = VecBloomFilter::<str, _>::new(1000, 4, builder()).unwrap();
+
+        let res = filter1.estimated_intersection_len(&filter2);
+        assert_eq!(res, Err(Error::IncompatibleSizes));
+
+        let res = filter1 | filter2;
+        assert_eq!(res.err(), Some(Error::IncompatibleSizes));
+    }
+}
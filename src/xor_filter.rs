@@ -0,0 +1,423 @@
+use crate::{builder_fingerprint, Error};
+use aabel_multihash_rs::{BuildHasherExt, HasherExt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    ops::BitXor,
+};
+
+/// The maximum number of seeds tried when building a static filter.
+pub(crate) const MAX_ATTEMPTS: usize = 100;
+
+/// The fingerprints stored by the static filters.
+pub trait Fingerprint:
+    Copy + Default + Eq + BitXor<Output = Self> + Into<u64> + TryFrom<u64>
+{
+    /// The number of bits of a fingerprint.
+    const BITS: u32;
+
+    /// Computes the fingerprint of a hash value.
+    fn from_hash(hash: u64) -> Self;
+}
+
+impl Fingerprint for u8 {
+    const BITS: u32 = u8::BITS;
+
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u8
+    }
+}
+
+impl Fingerprint for u16 {
+    const BITS: u32 = u16::BITS;
+
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u16
+    }
+}
+
+/// Computes the sorted and deduplicated first hash values of the keys.
+pub(crate) fn key_hashes<T, B, I>(keys: I, builder: &B) -> Vec<u64>
+where
+    T: Hash + ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+{
+    let mut hashes = keys
+        .into_iter()
+        .map(|key| first_hash(key.borrow(), builder))
+        .collect::<Vec<_>>();
+
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+/// Computes the first hash value of an item.
+pub(crate) fn first_hash<U, B>(item: &U, builder: &B) -> u64
+where
+    U: Hash + ?Sized,
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+{
+    builder
+        .hashes_one(item)
+        .next()
+        .expect("the hash sequence is infinite")
+        .into()
+}
+
+/// Mixes a hash value with a seed. Distinct hash values stay distinct for the same seed.
+pub(crate) fn mix(hash: u64, seed: u64) -> u64 {
+    let mut h = hash.wrapping_add(seed);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Computes the fingerprints of a filter where the fingerprint of each key is the xor of the three slots
+/// given by `positions`, which are distinct. The hypergraph with an edge for each key is peeled by repeatedly
+/// removing the keys which are the only ones mapped to one of their slots, and then the fingerprints are
+/// assigned in reverse order. It returns `None` when the hypergraph cannot be peeled.
+pub(crate) fn assign_fingerprints<F, P>(
+    hashes: &[u64],
+    slots: usize,
+    positions: P,
+) -> Option<Vec<F>>
+where
+    F: Fingerprint,
+    P: Fn(u64) -> [usize; 3],
+{
+    // The xor of the hashes mapped to each slot gives the remaining key once a single one is left.
+    let mut masks = vec![0u64; slots];
+    let mut counts = vec![0u32; slots];
+
+    for &hash in hashes {
+        for slot in positions(hash) {
+            masks[slot] ^= hash;
+            counts[slot] += 1;
+        }
+    }
+
+    let mut queue = (0..slots)
+        .filter(|slot| counts[*slot] == 1)
+        .collect::<Vec<_>>();
+    let mut stack = Vec::with_capacity(hashes.len());
+
+    while let Some(slot) = queue.pop() {
+        if counts[slot] != 1 {
+            continue;
+        }
+
+        let hash = masks[slot];
+        stack.push((hash, slot));
+
+        for other in positions(hash) {
+            masks[other] ^= hash;
+            counts[other] -= 1;
+            if counts[other] == 1 {
+                queue.push(other);
+            }
+        }
+    }
+
+    if stack.len() < hashes.len() {
+        return None;
+    }
+
+    let mut fingerprints = vec![F::default(); slots];
+    for (hash, slot) in stack.into_iter().rev() {
+        let [a, b, c] = positions(hash);
+        fingerprints[slot] =
+            F::from_hash(hash) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+    }
+
+    Some(fingerprints)
+}
+
+/// Maps a 32 bits hash value to the interval [0, n).
+fn reduce(hash: u32, n: usize) -> usize {
+    ((hash as u64 * n as u64) >> 32) as usize
+}
+
+/// Implements the [xor filter](https://doi.org/10.1145/3376122) of Graf and Lemire, a static filter
+/// built once from a complete set of keys. The slots are split into three blocks and each key is mapped
+/// to one slot of each block, such that the xor of the three fingerprints is the fingerprint of the key.
+///
+/// It uses about `1.23 F` bits per key, where `F` is the number of bits of the [`Fingerprint`],
+/// and its false-positive probability is `2^-F`.
+///
+/// # Example
+///
+///```
+/// use aabel_bloom_rs::XorFilter8;
+/// use aabel_multihash_rs::BuildPairHasher;
+///
+/// let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+/// let filter = XorFilter8::<str, _>::new(["Hello", "world!"], builder).unwrap();
+///
+/// assert!(filter.contains("Hello"));
+/// assert!(filter.contains("world!"));
+///```
+pub struct XorFilter<T, B, F = u8>
+where
+    T: ?Sized,
+{
+    builder: B,
+    seed: u64,
+    fingerprints: Vec<F>,
+    block_len: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+/// A [`XorFilter`] with fingerprints of 8 bits.
+pub type XorFilter8<T, B> = XorFilter<T, B, u8>;
+
+/// A [`XorFilter`] with fingerprints of 16 bits.
+pub type XorFilter16<T, B> = XorFilter<T, B, u16>;
+
+impl<T, B, F> XorFilter<T, B, F>
+where
+    T: ?Sized,
+    F: Fingerprint,
+{
+    /// Returns the number of distinct keys in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the filter was built from an empty set of keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn bit_len(&self) -> usize {
+        self.fingerprints.len() * F::BITS as usize
+    }
+
+    /// Returns the number of bits of the filter for each key.
+    pub fn bits_per_key(&self) -> f64 {
+        self.bit_len() as f64 / self.len.max(1) as f64
+    }
+
+    /// Returns the false-positive probability `2^-F`.
+    pub fn false_positive_rate(&self) -> f64 {
+        0.5f64.powi(F::BITS as i32)
+    }
+
+    fn positions(hash: u64, block_len: usize) -> [usize; 3] {
+        [
+            reduce(hash as u32, block_len),
+            reduce(hash.rotate_left(21) as u32, block_len) + block_len,
+            reduce(hash.rotate_left(42) as u32, block_len) + 2 * block_len,
+        ]
+    }
+}
+
+impl<T, B, F> XorFilter<T, B, F>
+where
+    B: BuildHasher + BuildHasherExt,
+    <B as BuildHasher>::Hasher: HasherExt,
+    T: Hash + ?Sized,
+    F: Fingerprint,
+{
+    /// Builds a new [`XorFilter`] instance from a set of keys. Duplicated keys are ignored.
+    /// It returns [`Error::ConstructionFailed`] when no seed derived from the hasher builder
+    /// leads to a valid filter, which is very unlikely.
+    pub fn new<I>(keys: I, builder: B) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let hashes = key_hashes(keys, &builder);
+        let capacity = (1.23 * hashes.len() as f64) as usize + 32;
+        let block_len = capacity / 3;
+        let mut rng = StdRng::seed_from_u64(builder_fingerprint(&builder));
+
+        for _ in 0..MAX_ATTEMPTS {
+            let seed = rng.gen();
+            let mixed = hashes
+                .iter()
+                .map(|hash| mix(*hash, seed))
+                .collect::<Vec<_>>();
+            let positions = |hash| Self::positions(hash, block_len);
+
+            if let Some(fingerprints) = assign_fingerprints(&mixed, 3 * block_len, positions) {
+                return Ok(Self {
+                    builder,
+                    seed,
+                    fingerprints,
+                    block_len,
+                    len: hashes.len(),
+                    _marker: PhantomData,
+                });
+            }
+        }
+
+        Err(Error::ConstructionFailed)
+    }
+
+    /// Checks if a given item is present in the filter.
+    pub fn contains<U>(&self, item: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Hash + ?Sized,
+    {
+        let hash = mix(first_hash(item, &self.builder), self.seed);
+        let [a, b, c] = Self::positions(hash, self.block_len);
+
+        F::from_hash(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use crate::{
+        builder_fingerprint,
+        serde_support::{check_hasher, check_size, de_error, Bytes},
+    };
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The serialized form of a [`XorFilter`]. The width of the fingerprints rejects a filter deserialized
+    /// with a different [`Fingerprint`] type.
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        hasher: u64,
+        fingerprint_bits: u32,
+        seed: u64,
+        block_len: usize,
+        len: usize,
+        fingerprints: Bytes,
+    }
+
+    impl<T, B, F> Serialize for XorFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let fingerprints = self
+                .fingerprints
+                .iter()
+                .map(|fingerprint| (*fingerprint).into());
+
+            Repr {
+                hasher: builder_fingerprint(&self.builder),
+                fingerprint_bits: F::BITS,
+                seed: self.seed,
+                block_len: self.block_len,
+                len: self.len,
+                fingerprints: Bytes::from_values(fingerprints, F::BITS),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T, B, F> Deserialize<'de> for XorFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt + Default,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Self::deserialize_with(deserializer, B::default())
+        }
+    }
+
+    impl<T, B, F> XorFilter<T, B, F>
+    where
+        T: ?Sized,
+        B: BuildHasher + BuildHasherExt,
+        <B as BuildHasher>::Hasher: HasherExt,
+        F: Fingerprint,
+    {
+        /// Deserializes a filter with a given hasher builder, for the builders which do not implement [`Default`].
+        /// It fails when the fingerprints have a different width or when the filter was built with a different
+        /// hasher builder.
+        pub fn deserialize_with<'de, D: Deserializer<'de>>(
+            deserializer: D,
+            builder: B,
+        ) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            Self::from_repr(repr, builder).map_err(de_error)
+        }
+
+        fn from_repr(repr: Repr, builder: B) -> Result<Self, Error> {
+            check_size(repr.fingerprint_bits, F::BITS)?;
+            check_hasher(repr.hasher, &builder)?;
+
+            if repr.block_len == 0 {
+                return Err(Error::ZeroSize("block length"));
+            }
+
+            let fingerprints = repr
+                .fingerprints
+                .into_values(repr.block_len.saturating_mul(3), F::BITS)?
+                .into_iter()
+                .map(|value| F::try_from(value).unwrap_or_default())
+                .collect();
+
+            Ok(Self {
+                builder,
+                seed: repr.seed,
+                fingerprints,
+                block_len: repr.block_len,
+                len: repr.len,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aabel_multihash_rs::BuildPairHasher;
+
+    #[test]
+    fn no_false_negatives() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = XorFilter8::<u32, _>::new(0..10_000u32, builder).unwrap();
+
+        assert_eq!(filter.len(), 10_000);
+        assert!((0..10_000u32).all(|i| filter.contains(&i)));
+        assert!(filter.bits_per_key() < 10.0);
+
+        let false_positives = (10_000..110_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 600);
+    }
+
+    #[test]
+    fn fingerprints_16() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let keys = (0..1000u32).chain(0..1000u32);
+        let filter = XorFilter16::<u32, _>::new(keys, builder).unwrap();
+
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000u32).all(|i| filter.contains(&i)));
+
+        let false_positives = (1000..101_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 10);
+    }
+
+    #[test]
+    fn empty() {
+        let builder = BuildPairHasher::new_with_keys((0, 0), (1, 1));
+        let filter = XorFilter8::<String, _>::new(Vec::<String>::new(), builder).unwrap();
+
+        assert!(filter.is_empty());
+        assert!(!filter.contains("Hello world!"));
+    }
+}
@@ -0,0 +1,204 @@
+/// The primes shared with [xxHash64](https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md).
+const PRIME32_1: u64 = 0x9e37_79b1;
+const PRIME32_2: u64 = 0x85eb_ca77;
+const PRIME32_3: u64 = 0xc2b2_ae3d;
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+/// The default secret of the XXH3 preview.
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+/// The number of bytes consumed by each accumulation.
+const STRIPE_LEN: usize = 64;
+
+/// The number of secret bytes skipped between two stripes.
+const SECRET_CONSUME_RATE: usize = 8;
+
+/// The number of stripes accumulated before scrambling the accumulators.
+const STRIPES_PER_BLOCK: usize = (SECRET.len() - STRIPE_LEN) / SECRET_CONSUME_RATE;
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn mul128_fold64(lhs: u64, rhs: u64) -> u64 {
+    let product = lhs as u128 * rhs as u128;
+    product as u64 ^ (product >> 64) as u64
+}
+
+fn avalanche(mut hash: u64) -> u64 {
+    hash ^= hash >> 37;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^ (hash >> 32)
+}
+
+fn mix16(bytes: &[u8], secret: &[u8]) -> u64 {
+    mul128_fold64(
+        read_u64(bytes) ^ read_u64(secret),
+        read_u64(&bytes[8..]) ^ read_u64(&secret[8..]),
+    )
+}
+
+fn accumulate(acc: &mut [u64; 8], stripe: &[u8], secret: &[u8]) {
+    acc.iter_mut().enumerate().for_each(|(i, acc)| {
+        let value = read_u64(&stripe[8 * i..]);
+        let key = value ^ read_u64(&secret[8 * i..]);
+        *acc = acc
+            .wrapping_add(value)
+            .wrapping_add((key & 0xffff_ffff) * (key >> 32));
+    });
+}
+
+fn scramble(acc: &mut [u64; 8], secret: &[u8]) {
+    acc.iter_mut().enumerate().for_each(|(i, acc)| {
+        let hash = *acc ^ (*acc >> 47) ^ read_u64(&secret[8 * i..]);
+        *acc = hash.wrapping_mul(PRIME32_1);
+    });
+}
+
+fn hash_long(bytes: &[u8]) -> u64 {
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+
+    let block_len = STRIPE_LEN * STRIPES_PER_BLOCK;
+    let blocks = bytes.len() / block_len;
+
+    for block in bytes.chunks_exact(block_len).take(blocks) {
+        block
+            .chunks_exact(STRIPE_LEN)
+            .enumerate()
+            .for_each(|(n, stripe)| {
+                accumulate(&mut acc, stripe, &SECRET[n * SECRET_CONSUME_RATE..])
+            });
+        scramble(&mut acc, &SECRET[SECRET.len() - STRIPE_LEN..]);
+    }
+
+    // The stripes of the last partial block, then the last 64 bytes when the input is not made of whole stripes.
+    let rest = &bytes[blocks * block_len..];
+    rest.chunks_exact(STRIPE_LEN)
+        .enumerate()
+        .for_each(|(n, stripe)| accumulate(&mut acc, stripe, &SECRET[n * SECRET_CONSUME_RATE..]));
+
+    if !bytes.len().is_multiple_of(STRIPE_LEN) {
+        let stripe = &bytes[bytes.len() - STRIPE_LEN..];
+        accumulate(&mut acc, stripe, &SECRET[SECRET.len() - STRIPE_LEN - 7..]);
+    }
+
+    let start = (bytes.len() as u64).wrapping_mul(PRIME64_1);
+    let hash = (0..4).fold(start, |hash, i| {
+        let secret = &SECRET[11 + 16 * i..];
+        hash.wrapping_add(mul128_fold64(
+            acc[2 * i] ^ read_u64(secret),
+            acc[2 * i + 1] ^ read_u64(&secret[8..]),
+        ))
+    });
+
+    avalanche(hash)
+}
+
+/// Computes the 64 bits hash of some bytes with the preview of XXH3 vendored by RocksDB, as `XXPH3_64bits`,
+/// which is not the final XXH3. RocksDB hashes the keys of its filters with it, and the empty input with a
+/// modification of the preview.
+pub(crate) fn xxph3_64(bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+
+    match len {
+        0 => mul128_fold64(read_u64(&SECRET), PRIME64_2),
+        1..=3 => {
+            let combined = bytes[0] as u32
+                | (bytes[len >> 1] as u32) << 8
+                | (bytes[len - 1] as u32) << 16
+                | (len as u32) << 24;
+            let keyed = combined as u64 ^ read_u32(&SECRET) as u64;
+            avalanche(keyed.wrapping_mul(PRIME64_1))
+        }
+        4..=8 => {
+            let input = read_u32(bytes) as u64 | (read_u32(&bytes[len - 4..]) as u64) << 32;
+            let keyed = input ^ read_u64(&SECRET);
+            let mix = (len as u64).wrapping_add((keyed ^ (keyed >> 51)).wrapping_mul(PRIME32_1));
+            avalanche((mix ^ (mix >> 47)).wrapping_mul(PRIME64_2))
+        }
+        9..=16 => {
+            let lo = read_u64(bytes) ^ read_u64(&SECRET);
+            let hi = read_u64(&bytes[len - 8..]) ^ read_u64(&SECRET[8..]);
+            let acc = (len as u64)
+                .wrapping_add(lo.wrapping_add(hi))
+                .wrapping_add(mul128_fold64(lo, hi));
+            avalanche(acc)
+        }
+        17..=128 => {
+            // The pairs of 16 bytes chunks taken from both ends of the input, from the outside in.
+            let pairs = (len - 1) / 32 + 1;
+            let acc = (0..pairs).fold((len as u64).wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&bytes[16 * i..], &SECRET[32 * i..]))
+                    .wrapping_add(mix16(&bytes[len - 16 * (i + 1)..], &SECRET[32 * i + 16..]))
+            });
+            avalanche(acc)
+        }
+        129..=240 => {
+            let acc = (0..8).fold((len as u64).wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&bytes[16 * i..], &SECRET[16 * i..]))
+            });
+            let acc = (8..len / 16).fold(avalanche(acc), |acc, i| {
+                acc.wrapping_add(mix16(&bytes[16 * i..], &SECRET[16 * (i - 8) + 3..]))
+            });
+            avalanche(acc.wrapping_add(mix16(&bytes[len - 16..], &SECRET[136 - 17..])))
+        }
+        _ => hash_long(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xxph3_vectors() {
+        // Generated with `Hash64` of RocksDB, over the bytes `(31 * i + 7) % 256`.
+        let bytes: Vec<u8> = (0..1100u32).map(|i| (i * 31 + 7) as u8).collect();
+        let vectors = [
+            (0, 0x5342_c301_0fe1_dd04),
+            (1, 0xb71d_295c_7865_c7e9),
+            (3, 0xf3eb_56c8_cb2d_fcd1),
+            (5, 0xacc1_9d54_6ae1_d058),
+            (9, 0x5c19_ea18_40fa_f370),
+            (16, 0x59fe_56a0_5385_4571),
+            (17, 0x21f1_4a7e_e3c4_4027),
+            (33, 0xb88d_0201_83c0_2b66),
+            (97, 0x3fd4_e930_8b00_8d53),
+            (128, 0x0a11_2f2a_5120_3d48),
+            (129, 0xbc91_1b7c_684b_fbfd),
+            (240, 0x926e_da7a_0b8e_acf1),
+            (241, 0x57c1_4ddd_1d4b_8f69),
+            (256, 0x5b25_d38f_a9f0_e7e5),
+            (1024, 0x4740_611a_913a_8e75),
+            (1100, 0xbe1b_3218_6095_9466),
+        ];
+
+        vectors
+            .iter()
+            .for_each(|(len, hash)| assert_eq!(xxph3_64(&bytes[..*len]), *hash, "length {len}"));
+        assert_eq!(xxph3_64(b"abc"), 0xd39e_eb71_bb53_42e8);
+    }
+}
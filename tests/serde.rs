@@ -0,0 +1,262 @@
+#![cfg(feature = "serde")]
+
+use aabel_bloom_rs::*;
+use aabel_multihash_rs::{Hash64, HasherExt};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A hasher builder with a fixed seed, which can be created by [`Default`] unlike the builders
+/// of `aabel_multihash_rs`.
+#[derive(Default, Clone)]
+struct Builder(u64);
+
+/// Generates the sequence `a + i * b` from two hashers with different keys.
+struct PairHasher(DefaultHasher, DefaultHasher);
+
+impl BuildHasher for Builder {
+    type Hasher = PairHasher;
+
+    fn build_hasher(&self) -> PairHasher {
+        let mut hasher = PairHasher(DefaultHasher::new(), DefaultHasher::new());
+        hasher.0.write_u64(self.0);
+        hasher.1.write_u64(!self.0);
+        hasher
+    }
+}
+
+impl Hasher for PairHasher {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+        self.1.write(bytes);
+    }
+}
+
+impl HasherExt for PairHasher {
+    fn finish_iter(self) -> impl Iterator<Item = Hash64> {
+        let (a, b) = (self.0.finish(), self.1.finish());
+        (0u64..).map(move |i| Hash64::new(a.wrapping_add(i.wrapping_mul(b))))
+    }
+}
+
+fn other_builder() -> Builder {
+    Builder(1)
+}
+
+#[test]
+fn bloom_filter() {
+    let mut filter = BloomFilter::<u32, Builder>::new(Builder::default());
+    (0..100u32).for_each(|i| filter.insert(&i));
+
+    let json = serde_json::to_string(&filter).unwrap();
+    assert!(json.len() < 2 * 100 * 8);
+    assert!(!json.contains("true"));
+
+    let mut other: BloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| other.contains(&i)));
+    assert_eq!(serde_json::to_string(&other).unwrap(), json);
+
+    let atomic = AtomicBloomFilter::<u32, Builder>::new(Builder::default());
+    (0..100u32).for_each(|i| atomic.insert(&i));
+    assert_eq!(serde_json::to_string(&atomic.snapshot()).unwrap(), json);
+
+    let res = serde_json::from_str::<BloomFilter<u32, Builder, 100, 7>>(&json);
+    assert!(res.err().unwrap().to_string().contains("different sizes"));
+
+    let res = serde_json::from_str::<BloomFilter<u32, Builder, 50>>(&json);
+    assert!(res.err().unwrap().to_string().contains("different sizes"));
+
+    let mut de = serde_json::Deserializer::from_str(&json);
+    let res = BloomFilter::<u32, Builder>::deserialize_with(&mut de, other_builder());
+    assert!(res
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("different hasher builders"));
+}
+
+#[test]
+fn dynamic_filters() {
+    let mut filter = VecBloomFilter::<u32, Builder>::new(1000, 7, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: VecBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| filter.contains(&i)));
+
+    let mut filter = CountingBloomFilter::<u32, Builder>::new(1000, 7, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: CountingBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| filter.contains(&i)));
+    assert!(serde_json::from_str::<CountingBloomFilter<u32, Builder, Counters8>>(&json).is_err());
+
+    let mut filter = BlockedBloomFilter::<u32, Builder>::new(10, 7, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: BlockedBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| filter.contains(&i)));
+
+    let mut filter =
+        PartitionedBloomFilter::<u32, Builder>::new(100, 7, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: PartitionedBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| filter.contains(&i)));
+
+    let mut filter =
+        ScalableBloomFilter::<u32, Builder>::new(10, 0.01, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: ScalableBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..100u32).all(|i| other.contains(&i)));
+    assert_eq!(other.len(), filter.len());
+
+    let mut value = serde_json::to_value(&filter).unwrap();
+    value["slices"][0]["capacity"] = 0.into();
+    let res = serde_json::from_value::<ScalableBloomFilter<u32, Builder>>(value);
+    assert!(res
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("sub-filter capacity"));
+
+    let mut value = serde_json::to_value(&filter).unwrap();
+    value["slices"][0]["fpr"] = 1.5.into();
+    let res = serde_json::from_value::<ScalableBloomFilter<u32, Builder>>(value);
+    assert!(res
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("sub-filter probability"));
+
+    let mut filter = SplitBlockBloomFilter::with_capacity_and_fpr(100, 0.01).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: SplitBlockBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(other, filter);
+
+    let mut filter = GuavaBloomFilter::with_capacity_and_fpr(100, 0.01).unwrap();
+    (0..100i32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: GuavaBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(other, filter);
+
+    let mut filter = RedisBloomFilter::new(10, 0.01).unwrap();
+    (0..100u32).for_each(|i| _ = filter.insert(&i.to_le_bytes()).unwrap());
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: RedisBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(other, filter);
+
+    let mut block = vec![0x5a; 64];
+    block.extend_from_slice(&[0xff, 0, 6, 0, 0]);
+    let filter = RocksDbBloomFilter::from_bytes(&block).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: RocksDbBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(other, filter);
+
+    let mut bytes = vec![0, 0, 0, 3, 0, 0, 0, 2];
+    bytes.extend_from_slice(&[0x5a; 16]);
+    let filter =
+        CassandraBloomFilter::from_bytes(&bytes, CassandraFilterFormat::Cassandra3).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: CassandraBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(other, filter);
+}
+
+#[test]
+fn stateful_filters() {
+    // The restored filters continue the random choices of the original ones.
+    let mut filter =
+        StableBloomFilter::<u32, Builder>::new(1000, 3, 3, 10, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i));
+    let json = serde_json::to_string(&filter).unwrap();
+    let mut other: StableBloomFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    (100..200u32).for_each(|i| {
+        filter.insert(&i);
+        other.insert(&i);
+    });
+    assert_eq!(
+        serde_json::to_string(&filter).unwrap(),
+        serde_json::to_string(&other).unwrap()
+    );
+
+    let mut filter = CuckooFilter::<u32, Builder>::new(100, 12, 4, Builder::default()).unwrap();
+    (0..90u32).for_each(|i| filter.insert(&i).unwrap());
+    let json = serde_json::to_string(&filter).unwrap();
+    let mut other: CuckooFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert_eq!(other.len(), 90);
+    assert!((0..90u32).all(|i| other.contains(&i)));
+    assert!(other.remove(&0));
+
+    let mut filter = QuotientFilter::<u32, Builder>::new(8, 8, Builder::default()).unwrap();
+    (0..100u32).for_each(|i| filter.insert(&i).unwrap());
+    let json = serde_json::to_string(&filter).unwrap();
+    let other: QuotientFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert_eq!(other.len(), 100);
+    assert!(other.fingerprints().eq(filter.fingerprints()));
+
+    // A filter needs an empty slot, so 16 fingerprints do not fit in 16 slots.
+    let filter = QuotientFilter::<u32, Builder>::new(4, 4, Builder::default()).unwrap();
+    let mut value = serde_json::to_value(&filter).unwrap();
+    value["len"] = 16.into();
+    value["fingerprints"] = "/////////////////////w==".into();
+    let res = serde_json::from_value::<QuotientFilter<u32, Builder>>(value);
+    assert!(res.err().unwrap().to_string().contains("number of items"));
+}
+
+#[test]
+fn static_filters() {
+    let filter = XorFilter16::<u32, Builder>::new(0..1000u32, Builder::default()).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: XorFilter16<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..1000u32).all(|i| filter.contains(&i)));
+    assert!(serde_json::from_str::<XorFilter8<u32, Builder>>(&json).is_err());
+
+    let filter = BinaryFuseFilter8::<u32, Builder>::new(0..1000u32, Builder::default()).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: BinaryFuseFilter8<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..1000u32).all(|i| filter.contains(&i)));
+
+    let filter = RibbonFilter::<u32, Builder>::new(0..1000u32, 9, 32, Builder::default()).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let filter: RibbonFilter<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!((0..1000u32).all(|i| filter.contains(&i)));
+}
+
+#[test]
+fn sketches() {
+    let mut sketch = CountMinSketch::<u32, Builder>::new(100, 4, Builder::default())
+        .unwrap()
+        .with_conservative_update(true);
+    (0..100u32).for_each(|i| sketch.add(&i, i as u64));
+    let json = serde_json::to_string(&sketch).unwrap();
+    let sketch: CountMinSketch<u32, Builder> = serde_json::from_str(&json).unwrap();
+    assert!(sketch.is_conservative());
+    assert!((0..100u32).all(|i| sketch.estimate(&i) >= i as u64));
+
+    let mut a =
+        InvertibleBloomFilter::<u64, Builder>::with_capacity(10, Builder::default()).unwrap();
+    let mut b =
+        InvertibleBloomFilter::<u64, Builder>::with_capacity(10, Builder::default()).unwrap();
+    (0..100u64).for_each(|i| a.insert(&i));
+    (1..100u64).for_each(|i| b.insert(&i));
+    let json = serde_json::to_string(&b).unwrap();
+    let b: InvertibleBloomFilter<u64, Builder> = serde_json::from_str(&json).unwrap();
+    a.subtract(&b).unwrap();
+    assert_eq!(a.decode().unwrap().only_in_self, vec![0]);
+
+    let mut a = StrataEstimator::<u64, Builder>::new(Builder::default());
+    (0..1000u64).for_each(|i| a.insert(&i));
+    let json = serde_json::to_string(&a).unwrap();
+    let mut de = serde_json::Deserializer::from_str(&json);
+    let res = StrataEstimator::<u64, Builder>::deserialize_with(&mut de, other_builder());
+    assert!(res.is_err());
+    let b: StrataEstimator<u64, Builder> = serde_json::from_str(&json).unwrap();
+    a.subtract(&b).unwrap();
+    assert_eq!(a.estimate(), 0);
+}